@@ -23,6 +23,13 @@
 
 use engine_core::{window::{Key}};
 
+use unicode_segmentation::UnicodeSegmentation;
+
+use std::time::Instant;
+
+use crate::gui::TextFont;
+use crate::text;
+
 pub trait Bounds {
     fn bounds(&self) -> (f32, f32, f32, f32);
 }
@@ -33,7 +40,14 @@ pub struct Button {
     pub width: f32,
     pub height: f32,
     pub text: String,
-    pub pressed: bool,
+    pub pressed: bool, //held down, from a press inside until the mouse is released anywhere
+    pub repeat: bool, //clicks again and again while held, like the + and - of a spinner
+    pub next_repeat: Option<Instant>,
+    pub repeated: bool, //clicked by repeating since it was pressed, letting go doesn't click again
+    pub padding: Option<f32>, //overrides the style's padding
+    pub font: TextFont,
+    pub hovered: bool, //the mouse was over it when it was last drawn
+    pub disabled: bool, //drawn but takes no input or focus
 }
 
 impl Button {
@@ -45,22 +59,105 @@ impl Button {
             height: 1.0,
             text: String::new(),
             pressed: false,
+            repeat: false,
+            next_repeat: None,
+            repeated: false,
+            padding: None,
+            font: TextFont::Body,
+            hovered: false,
+            disabled: false,
         }
     }
 }
 
+//What happened to a button during one frame
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ButtonEvents {
+    pub clicked: bool, //released inside, activated by a key or repeated
+    pub pressed: bool,
+    pub released: bool, //wherever the mouse was
+    pub held: bool,
+}
+
 impl Bounds for Button {
     fn bounds(&self) -> (f32, f32, f32, f32) {
         (self.x, self.y, self.width, self.height)
     }
 }
 
+pub struct Label {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub text: String,
+    pub padding: Option<f32>,
+    pub font: TextFont,
+    pub hovered: bool,
+}
+
+impl Label {
+    pub fn new() -> Label {
+        Label {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+            text: String::new(),
+            padding: None,
+            font: TextFont::Body,
+            hovered: false,
+        }
+    }
+}
+
+impl Bounds for Label {
+    fn bounds(&self) -> (f32, f32, f32, f32) {
+        (self.x, self.y, self.width, self.height)
+    }
+}
+
+//A label whose text has inline markup, see markup::parse
+pub struct RichLabel {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub text: String,
+    pub padding: Option<f32>,
+    pub font: TextFont,
+    pub hovered: bool,
+}
+
+impl RichLabel {
+    pub fn new() -> RichLabel {
+        RichLabel {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+            text: String::new(),
+            padding: None,
+            font: TextFont::Body,
+            hovered: false,
+        }
+    }
+}
+
+impl Bounds for RichLabel {
+    fn bounds(&self) -> (f32, f32, f32, f32) {
+        (self.x, self.y, self.width, self.height)
+    }
+}
+
 pub struct CheckBox {
     pub x: f32,
     pub y: f32,
     pub width: f32,
     pub height: f32,
     pub pressed: bool,
+    pub hovered: bool,
+    pub disabled: bool,
 }
 
 impl CheckBox {
@@ -71,6 +168,8 @@ impl CheckBox {
             width: 1.0,
             height: 1.0,
             pressed: false,
+            hovered: false,
+            disabled: false,
         }
     }
 }
@@ -88,6 +187,8 @@ pub struct Slider {
     pub height: f32, 
     pub val: f32, 
     pub selected: bool,
+    pub hovered: bool, //over the knob
+    pub disabled: bool,
 }
 
 impl Slider {
@@ -99,6 +200,8 @@ impl Slider {
             height: 1.0,
             val: 0.0,
             selected: false,
+            hovered: false,
+            disabled: false,
         }
     }
 }
@@ -109,6 +212,239 @@ impl Bounds for Slider {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum EditKind {
+    Typing,
+    Paste,
+    Cut,
+    Delete,
+}
+
+struct TextState {
+    text: String,
+    caret: usize,
+    selection_anchor: Option<usize>,
+}
+
+//Undo and redo steps of a TextBox, at most depth undo steps are kept
+pub struct EditHistory {
+    pub depth: usize,
+    undo: Vec<TextState>,
+    redo: Vec<TextState>,
+    last_kind: Option<EditKind>,
+}
+
+impl EditHistory {
+    pub fn new(depth: usize) -> EditHistory {
+        EditHistory {
+            depth,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_kind: None,
+        }
+    }
+
+    //Makes the next typed text start a new undo step
+    pub fn break_merge(&mut self) {
+        self.last_kind = None;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.last_kind = None;
+    }
+}
+
+pub struct EditParts<'a> {
+    pub text: &'a mut String,
+    pub caret: &'a mut usize,
+    pub selection_anchor: &'a mut Option<usize>,
+    pub history: &'a mut EditHistory,
+    pub selected: &'a mut bool,
+    pub dragging: &'a mut bool,
+}
+
+impl<'a> EditParts<'a> {
+    fn selection(&self) -> Option<(usize, usize)> {
+        match *self.selection_anchor {
+            Some(anchor) if anchor != *self.caret => Some((anchor.min(*self.caret), anchor.max(*self.caret))),
+            _ => None,
+        }
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                self.text.replace_range(start..end, "");
+                *self.caret = start;
+                *self.selection_anchor = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn replace_selection(&mut self, text: &str) {
+        self.delete_selection();
+        *self.selection_anchor = None;
+        self.text.insert_str(*self.caret, text);
+        *self.caret += text.len();
+    }
+
+    fn state(&self) -> TextState {
+        TextState {
+            text: self.text.clone(),
+            caret: *self.caret,
+            selection_anchor: *self.selection_anchor,
+        }
+    }
+
+    fn restore(&mut self, state: TextState) -> TextState {
+        let current = self.state();
+        *self.text = state.text;
+        *self.caret = state.caret;
+        *self.selection_anchor = state.selection_anchor;
+        current
+    }
+
+    //Saves the text before an edit, consecutive typing is merged into one step
+    fn record(&mut self, kind: EditKind) {
+        let merge = kind == EditKind::Typing && self.history.last_kind == Some(EditKind::Typing);
+        self.history.last_kind = Some(kind);
+        if merge {
+            return;
+        }
+
+        let state = self.state();
+        self.history.undo.push(state);
+        self.history.redo.clear();
+
+        while self.history.undo.len() > self.history.depth {
+            self.history.undo.remove(0);
+        }
+    }
+}
+
+//Caret, selection and undo handling shared by the text widgets, caret and anchor are byte indices into the text
+pub trait TextEdit {
+    fn text(&self) -> &str;
+    fn caret(&self) -> usize;
+    fn selection_anchor(&self) -> Option<usize>;
+    fn edit_parts(&mut self) -> EditParts<'_>;
+
+    //Whether the selection may be copied or cut to the clipboard
+    fn can_copy(&self) -> bool {
+        true
+    }
+
+    //The selected byte range, None if nothing is selected
+    fn selection(&self) -> Option<(usize, usize)> {
+        match self.selection_anchor() {
+            Some(anchor) if anchor != self.caret() => Some((anchor.min(self.caret()), anchor.max(self.caret()))),
+            _ => None,
+        }
+    }
+
+    fn selected_text(&self) -> &str {
+        match self.selection() {
+            Some((start, end)) => &self.text()[start..end],
+            None => "",
+        }
+    }
+
+    //Moves the caret, extending the selection from where the caret was if extend is set
+    fn move_caret(&mut self, to: usize, extend: bool) {
+        let parts = self.edit_parts();
+        parts.history.break_merge();
+        if extend {
+            if parts.selection_anchor.is_none() {
+                *parts.selection_anchor = Some(*parts.caret);
+            }
+        } else {
+            *parts.selection_anchor = None;
+        }
+        *parts.caret = text::clamp_boundary(parts.text.as_str(), to);
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        self.edit_parts().delete_selection()
+    }
+
+    //Inserts typed text at the caret, replacing the selection if there is one
+    fn insert(&mut self, text: &str) {
+        let mut parts = self.edit_parts();
+        parts.record(EditKind::Typing);
+        parts.replace_selection(text);
+    }
+
+    fn paste(&mut self, text: &str) {
+        let mut parts = self.edit_parts();
+        parts.record(EditKind::Paste);
+        parts.replace_selection(text);
+    }
+
+    //Removes the selection and returns what was removed
+    fn cut(&mut self) -> String {
+        let cut = String::from(self.selected_text());
+        if !cut.is_empty() {
+            let mut parts = self.edit_parts();
+            parts.record(EditKind::Cut);
+            parts.delete_selection();
+        }
+        cut
+    }
+
+    fn backspace(&mut self) {
+        let mut parts = self.edit_parts();
+        if parts.selection().is_some() || *parts.caret > 0 {
+            parts.record(EditKind::Delete);
+        }
+        if !parts.delete_selection() && *parts.caret > 0 {
+            let prev = text::prev_boundary(parts.text.as_str(), *parts.caret);
+            parts.text.replace_range(prev..*parts.caret, "");
+            *parts.caret = prev;
+        }
+    }
+
+    fn delete(&mut self) {
+        let mut parts = self.edit_parts();
+        if parts.selection().is_some() || *parts.caret < parts.text.len() {
+            parts.record(EditKind::Delete);
+        }
+        if !parts.delete_selection() && *parts.caret < parts.text.len() {
+            let next = text::next_boundary(parts.text.as_str(), *parts.caret);
+            parts.text.replace_range(*parts.caret..next, "");
+        }
+    }
+
+    fn undo(&mut self) {
+        let mut parts = self.edit_parts();
+        if let Some(state) = parts.history.undo.pop() {
+            let current = parts.restore(state);
+            parts.history.redo.push(current);
+            parts.history.break_merge();
+        }
+    }
+
+    fn redo(&mut self) {
+        let mut parts = self.edit_parts();
+        if let Some(state) = parts.history.redo.pop() {
+            let current = parts.restore(state);
+            parts.history.undo.push(current);
+            parts.history.break_merge();
+        }
+    }
+}
+
 pub struct TextBox {
     pub x: f32,
     pub y: f32, 
@@ -116,6 +452,17 @@ pub struct TextBox {
     pub height: f32, 
     pub text: String, 
     pub selected: bool, 
+    pub caret: usize, //byte index into text
+    pub selection_anchor: Option<usize>, //where the selection started, it ends at the caret
+    pub dragging: bool,
+    pub history: EditHistory,
+    pub scroll: f32, //pixels of text scrolled out of view on the left
+    pub mask: Option<char>, //drawn in place of every character, for passwords
+    pub revealed: bool, //shows the text even if a mask is set
+    pub padding: Option<f32>,
+    pub font: TextFont,
+    pub hovered: bool,
+    pub disabled: bool,
 }
 
 impl TextBox {
@@ -127,6 +474,45 @@ impl TextBox {
             height: 1.0,
             text: String::new(),
             selected: false,
+            caret: 0,
+            selection_anchor: None,
+            dragging: false,
+            history: EditHistory::new(100),
+            scroll: 0.0,
+            mask: None,
+            revealed: false,
+            padding: None,
+            font: TextFont::Body,
+            hovered: false,
+            disabled: false,
+        }
+    }
+
+    pub fn is_masked(&self) -> bool {
+        self.mask.is_some() && !self.revealed
+    }
+
+    //The text as it is drawn
+    pub fn display_text(&self) -> String {
+        match self.mask {
+            Some(mask) if !self.revealed => self.text.graphemes(true).map(|_| mask).collect(),
+            _ => self.text.clone(),
+        }
+    }
+
+    //Byte index into display_text for a byte index into text
+    pub fn display_index(&self, index: usize) -> usize {
+        match self.mask {
+            Some(mask) if !self.revealed => text::count(&self.text[..index]) * mask.len_utf8(),
+            _ => index,
+        }
+    }
+
+    //Byte index into text for a byte index into display_text
+    pub fn text_index(&self, display_index: usize) -> usize {
+        match self.mask {
+            Some(mask) if !self.revealed => self.text.grapheme_indices(true).nth(display_index / mask.len_utf8()).map(|(i, _)| i).unwrap_or(self.text.len()),
+            _ => display_index,
         }
     }
 }
@@ -135,4 +521,198 @@ impl Bounds for TextBox {
     fn bounds(&self) -> (f32, f32, f32, f32) {
         (self.x, self.y, self.width, self.height)
     }
+}
+
+impl TextEdit for TextBox {
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn caret(&self) -> usize {
+        self.caret
+    }
+
+    fn selection_anchor(&self) -> Option<usize> {
+        self.selection_anchor
+    }
+
+    fn edit_parts(&mut self) -> EditParts<'_> {
+        EditParts {
+            text: &mut self.text,
+            caret: &mut self.caret,
+            selection_anchor: &mut self.selection_anchor,
+            history: &mut self.history,
+            selected: &mut self.selected,
+            dragging: &mut self.dragging,
+        }
+    }
+
+    fn can_copy(&self) -> bool {
+        !self.is_masked()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NumberKind {
+    Integer,
+    Float,
+}
+
+impl NumberKind {
+    //The number in text, None if it isn't one
+    pub fn parse(self, text: &str) -> Option<f64> {
+        let text = text.trim();
+        match self {
+            NumberKind::Integer => text.parse::<i64>().ok().map(|value| value as f64),
+            NumberKind::Float => text.parse::<f64>().ok().filter(|value| value.is_finite()),
+        }
+    }
+
+    //Whether ch can be typed or pasted into a field of this kind
+    pub fn accepts(self, ch: char) -> bool {
+        match self {
+            NumberKind::Integer => ch.is_ascii_digit() || ch == '-' || ch == '+',
+            NumberKind::Float => ch.is_ascii_digit() || ch == '-' || ch == '+' || ch == '.' || ch == 'e' || ch == 'E',
+        }
+    }
+}
+
+//A TextBox that only takes numbers, value is the last valid number typed clamped to min and max
+pub struct NumberField {
+    pub text_box: TextBox,
+    pub kind: NumberKind,
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+    pub step: f64, //added for every arrow press or wheel notch
+    pub precision: usize, //decimals shown for floats
+    pub valid: bool,
+}
+
+impl NumberField {
+    pub fn new(kind: NumberKind) -> NumberField {
+        let mut text_box = TextBox::new();
+        text_box.text = String::from("0");
+
+        NumberField {
+            text_box,
+            kind,
+            value: 0.0,
+            min: f64::MIN,
+            max: f64::MAX,
+            step: 1.0,
+            precision: 2,
+            valid: true,
+        }
+    }
+
+    //Whether ch can be typed into the field
+    pub fn accepts(&self, ch: char) -> bool {
+        self.kind.accepts(ch)
+    }
+
+    pub fn parse(&self) -> Option<f64> {
+        self.kind.parse(&self.text_box.text)
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        match self.kind {
+            NumberKind::Integer => format!("{}", value.round() as i64),
+            NumberKind::Float => format!("{:.*}", self.precision, value),
+        }
+    }
+
+    //Clamps value and writes it to the text as one undo step
+    pub fn set_value(&mut self, value: f64) {
+        let mut value = value.max(self.min).min(self.max);
+        if self.kind == NumberKind::Integer {
+            value = value.round();
+        }
+        self.value = value;
+        self.valid = true;
+
+        let text = self.format(value);
+        if text != self.text_box.text {
+            self.text_box.move_caret(0, false);
+            self.text_box.move_caret(self.text_box.text.len(), true);
+            self.text_box.paste(&text);
+        }
+    }
+}
+
+impl Bounds for NumberField {
+    fn bounds(&self) -> (f32, f32, f32, f32) {
+        self.text_box.bounds()
+    }
+}
+
+pub struct TextArea {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub text: String,
+    pub selected: bool,
+    pub caret: usize,
+    pub selection_anchor: Option<usize>,
+    pub dragging: bool,
+    pub history: EditHistory,
+    pub scroll: usize, //first visible line
+    pub padding: Option<f32>,
+    pub font: TextFont,
+    pub hovered: bool,
+    pub disabled: bool,
+}
+
+impl TextArea {
+    pub fn new() -> TextArea {
+        TextArea {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+            text: String::new(),
+            selected: false,
+            caret: 0,
+            selection_anchor: None,
+            dragging: false,
+            history: EditHistory::new(100),
+            scroll: 0,
+            padding: None,
+            font: TextFont::Body,
+            hovered: false,
+            disabled: false,
+        }
+    }
+}
+
+impl Bounds for TextArea {
+    fn bounds(&self) -> (f32, f32, f32, f32) {
+        (self.x, self.y, self.width, self.height)
+    }
+}
+
+impl TextEdit for TextArea {
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn caret(&self) -> usize {
+        self.caret
+    }
+
+    fn selection_anchor(&self) -> Option<usize> {
+        self.selection_anchor
+    }
+
+    fn edit_parts(&mut self) -> EditParts<'_> {
+        EditParts {
+            text: &mut self.text,
+            caret: &mut self.caret,
+            selection_anchor: &mut self.selection_anchor,
+            history: &mut self.history,
+            selected: &mut self.selected,
+            dragging: &mut self.dragging,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,184 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use engine_renderer::font::Font;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[cfg(feature = "embedded-font")]
+use std::path::Path;
+#[cfg(feature = "embedded-font")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::render::FontMetrics;
+use crate::ttf::{FontError, FontFile};
+
+//Compiled into the crate so text shows and can be measured without any resource files,
+//a subset of DejaVu Sans, see res/fonts/dejavu_sans_subset.LICENSE
+#[cfg(feature = "embedded-font")]
+static EMBEDDED_FONT: &[u8] = include_bytes!("../res/fonts/dejavu_sans_subset.ttf");
+
+pub const DEFAULT_FONT_SIZE: u32 = 32;
+
+//An engine Font with the file it was loaded from, Font can't be asked where its baseline is
+#[derive(Clone)]
+pub struct TrueTypeFont {
+    pub font: Font,
+    pub file: Option<Rc<FontFile>>, //None for a font that came without its file, it sits on the baseline
+
+    sizes: Option<(Rc<FontSizes>, u32)>, //where other sizes come from and the size of font, None without a path
+}
+
+//Every size of a font loaded so far, Font loads the whole file again for each size
+struct FontSizes {
+    path: String,
+    fonts: RefCell<HashMap<u32, Font>>,
+}
+
+impl TrueTypeFont {
+    pub fn load(path: &str, size: u32) -> Result<TrueTypeFont, FontError> {
+        let file = FontFile::open(path)?;
+        Ok(TrueTypeFont::with_file(path, size, file))
+    }
+
+    fn with_file(path: &str, size: u32, file: FontFile) -> TrueTypeFont {
+        let font = Font::new(path, size);
+
+        let mut fonts = HashMap::new();
+        fonts.insert(size, font.clone());
+
+        TrueTypeFont {
+            font,
+            file: Some(Rc::new(file)),
+            sizes: Some((Rc::new(FontSizes {
+                path: String::from(path),
+                fonts: RefCell::new(fonts),
+            }), size)),
+        }
+    }
+
+    pub fn from_font(font: Font) -> TrueTypeFont {
+        TrueTypeFont {
+            font,
+            file: None,
+            sizes: None,
+        }
+    }
+}
+
+impl FontMetrics for TrueTypeFont {
+    fn text_width(&self, text: &str) -> f32 {
+        self.font.text_width(text)
+    }
+
+    fn height(&self) -> f32 {
+        self.font.height() as f32
+    }
+
+    //a font without its file is taken to have every glyph, fallback fonts are never used in front of it
+    fn has_glyph(&self, ch: char) -> bool {
+        match &self.file {
+            Some(file) => file.glyph_index(ch).is_some(),
+            None => true,
+        }
+    }
+
+    //the height Font reports is split at the baseline in the proportions of the file's ascender and descender
+    fn ascent(&self) -> f32 {
+        match &self.file {
+            Some(file) => self.height() * file.ascent_fraction(),
+            None => self.height(),
+        }
+    }
+
+    fn descent(&self) -> f32 {
+        self.height() - self.ascent()
+    }
+
+    //Font's height grows with the size it's loaded at, so the size is scaled by how far off the height is
+    fn resized(&self, height: f32) -> TrueTypeFont {
+        let (sizes, current) = match &self.sizes {
+            Some((sizes, current)) if self.height() > 0.0 => (sizes, *current),
+            _ => return self.clone(),
+        };
+
+        let size = ((current as f32 * height / self.height()).round() as u32).max(1);
+        if size == current {
+            return self.clone();
+        }
+
+        let font = sizes.fonts.borrow_mut().entry(size).or_insert_with(|| Font::new(&sizes.path, size)).clone();
+        TrueTypeFont {
+            font,
+            file: self.file.clone(),
+            sizes: Some((sizes.clone(), size)),
+        }
+    }
+}
+
+//Font can only be loaded from a file, so the embedded one is written to the temp directory the first time it's needed.
+//The file is named after a hash of its contents and only ever replaced whole, other processes may be reading it
+#[cfg(feature = "embedded-font")]
+pub fn default_font(size: u32) -> Result<TrueTypeFont, FontError> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+    let file = FontFile::parse(EMBEDDED_FONT.to_vec())?;
+
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("engine_gui_default_font_{:016x}.ttf", fnv_hash(EMBEDDED_FONT)));
+
+    let written = std::fs::read(&path).map(|data| data == EMBEDDED_FONT).unwrap_or(false);
+    if !written {
+        let write = WRITES.fetch_add(1, Ordering::Relaxed);
+        let temp = dir.join(format!("engine_gui_default_font_{}_{}.tmp", std::process::id(), write));
+        std::fs::write(&temp, EMBEDDED_FONT)?;
+        if let Err(err) = std::fs::rename(&temp, &path) {
+            let _ = std::fs::remove_file(&temp);
+            return Err(err.into());
+        }
+    }
+
+    Ok(TrueTypeFont::with_file(&path.to_string_lossy(), size, file))
+}
+
+#[cfg(feature = "embedded-font")]
+fn fnv_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+//Loads the font at path, if there is no file there the embedded font is used instead
+#[cfg(feature = "embedded-font")]
+pub fn load_font(path: &str, size: u32) -> Result<TrueTypeFont, FontError> {
+    if Path::new(path).is_file() {
+        TrueTypeFont::load(path, size)
+    } else {
+        default_font(size)
+    }
+}
+
+#[cfg(not(feature = "embedded-font"))]
+pub fn load_font(path: &str, size: u32) -> Result<TrueTypeFont, FontError> {
+    TrueTypeFont::load(path, size)
+}
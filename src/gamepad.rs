@@ -0,0 +1,66 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GamepadButton {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Back,
+}
+
+//Where GUI::update pulls gamepad presses from, plug in a controller with GUI::set_gamepad.
+//A stick should send a direction once when it is pushed past its dead zone.
+pub trait Gamepad {
+    fn poll(&mut self) -> Option<GamepadButton>;
+}
+
+//Presses queued up by hand, clones share the queue so it can be filled after the GUI took one
+#[derive(Clone)]
+pub struct ScriptedGamepad {
+    presses: Rc<RefCell<VecDeque<GamepadButton>>>,
+}
+
+impl ScriptedGamepad {
+    pub fn new() -> ScriptedGamepad {
+        ScriptedGamepad {
+            presses: Rc::new(RefCell::new(VecDeque::new())),
+        }
+    }
+
+    pub fn press(&self, button: GamepadButton) {
+        self.presses.borrow_mut().push_back(button);
+    }
+}
+
+impl Gamepad for ScriptedGamepad {
+    fn poll(&mut self) -> Option<GamepadButton> {
+        self.presses.borrow_mut().pop_front()
+    }
+}
@@ -26,144 +26,643 @@ use engine_renderer::color::Color;
 use engine_renderer::graphics::Graphics;
 use engine_renderer::texture::TextureRegion;
 
-use crate::comps::*;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use unicode_segmentation::UnicodeSegmentation;
 
-pub enum TextAlign {
+use crate::clipboard::{Clipboard, MemoryClipboard};
+use crate::fonts::TrueTypeFont;
+use crate::gamepad::{Gamepad, GamepadButton, ScriptedGamepad};
+use crate::comps::*;
+use crate::text;
+use crate::input::{InputSource, WindowInput};
+use crate::markup::{self, Span};
+use crate::render::{Canvas, DrawCommand, FontMetrics, GraphicsCanvas, replay};
+use crate::raster::{Bitmap, ImageRegion};
+use crate::sdf::{SdfFont, SdfQuad};
+
+pub enum HorizontalAlign {
+    Left,
     Center,
-    LowerLeft,
-    LowerRight,
-    UpperLeft,
-    UpperRight,
+    Right,
 }
 
-pub struct GUIStyle {
-    pub foreground_color: Color,
-    pub background_color: Color,
-    pub text_color: Color,
+pub enum VerticalAlign {
+    Top,
+    Middle,
+    Bottom,
+    Baseline, //the baseline on the bottom padding edge, descenders hang into the padding
+}
 
-    pub foreground_texture: TextureRegion,
-    pub background_texture: TextureRegion,
+pub struct TextAlign {
+    pub horizontal: HorizontalAlign,
+    pub vertical: VerticalAlign,
+}
 
-    pub text_align: TextAlign,
+impl TextAlign {
+    pub fn new(horizontal: HorizontalAlign, vertical: VerticalAlign) -> TextAlign {
+        TextAlign {
+            horizontal,
+            vertical,
+        }
+    }
+}
+
+//What labels do with text wider than their box
+pub enum TextOverflow {
+    Clip,
+    Ellipsis,
+    MiddleEllipsis,
+    Wrap,
+}
+
+//Which modifier keys are held down, caps_lock is whether it's toggled on
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub super_key: bool,
+    pub caps_lock: bool,
+}
 
-    pub check_box_foreground_color: Color,
-    pub check_box_background_color: Color,
+const MOUSE_BUTTONS: usize = 8;
 
-    pub check_box_foreground_texture: TextureRegion,
-    pub check_box_background_texture: TextureRegion,
+//Glyphs whose spans are kept before they are all thrown away, text at ever changing sizes would fill it up
+const MAX_CACHED_GLYPHS: usize = 1024;
+
+//Which look a widget is drawn with, disabled beats active beats hovered beats focused
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WidgetState {
+    Normal,
+    Hovered,
+    Active, //pressed, checked, dragged or being edited
+    Focused,
+    Disabled,
+}
+
+impl WidgetState {
+    pub fn new(disabled: bool, active: bool, hovered: bool, focused: bool) -> WidgetState {
+        if disabled {
+            WidgetState::Disabled
+        } else if active {
+            WidgetState::Active
+        } else if hovered {
+            WidgetState::Hovered
+        } else if focused {
+            WidgetState::Focused
+        } else {
+            WidgetState::Normal
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct StateStyle<T = TextureRegion> {
+    pub color: Color,
+    pub texture: T,
+}
+
+//The look of one kind of widget in each of its states
+#[derive(Clone)]
+pub struct WidgetStyle<T = TextureRegion> {
+    pub normal: StateStyle<T>,
+    pub hovered: StateStyle<T>,
+    pub active: StateStyle<T>,
+    pub focused: StateStyle<T>,
+    pub disabled: StateStyle<T>,
+}
+
+impl<T: Clone> WidgetStyle<T> {
+    pub fn new(texture: T) -> WidgetStyle<T> {
+        WidgetStyle {
+            normal: StateStyle { color: Color::from(0xAAAAAAFFu32), texture: texture.clone() },
+            hovered: StateStyle { color: Color::from(0xBBBBBBFFu32), texture: texture.clone() },
+            active: StateStyle { color: Color::from(0x666666FFu32), texture: texture.clone() },
+            focused: StateStyle { color: Color::from(0xAAAAAAFFu32), texture: texture.clone() },
+            disabled: StateStyle { color: Color::from(0x888888FFu32), texture },
+        }
+    }
+
+    //The active texture for the active state and the normal one for all others
+    pub fn set_textures(&mut self, normal: T, active: T) {
+        self.normal.texture = normal.clone();
+        self.hovered.texture = normal.clone();
+        self.focused.texture = normal.clone();
+        self.disabled.texture = normal;
+        self.active.texture = active;
+    }
+
+    pub fn get(&self, state: WidgetState) -> &StateStyle<T> {
+        match state {
+            WidgetState::Normal => &self.normal,
+            WidgetState::Hovered => &self.hovered,
+            WidgetState::Active => &self.active,
+            WidgetState::Focused => &self.focused,
+            WidgetState::Disabled => &self.disabled,
+        }
+    }
+}
+
+//Which of the style's fonts a widget draws its text with
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextFont {
+    Body,
+    Heading,
+    Monospace,
+}
+
+pub struct GUIStyle<T = TextureRegion, F = TrueTypeFont> {
+    pub button: WidgetStyle<T>,
+    pub check_box: WidgetStyle<T>, //active while checked
+    pub slider: WidgetStyle<T>, //the knob, the bar is drawn normal or disabled
+    pub text_box: WidgetStyle<T>, //text boxes, number fields and text areas, active while edited
+
+    pub text_color: Color,
+    pub disabled_text_color: Color,
+
+    pub text_align: TextAlign,
+    pub text_overflow: TextOverflow,
+    pub padding: f32, //pixels between the edge of a widget and its text
+
+    //None keeps the font set on graphics, heading and monospace fall back to the body font
+    pub body_font: Option<F>,
+    pub heading_font: Option<F>,
+    pub monospace_font: Option<F>,
+    pub fallback_fonts: Vec<F>, //tried in order for characters the widget's font has no glyph for
+    //Height of the text from the fonts above in the units of the widget bounds, so it follows the transform.
+    //None draws them at their own size
+    pub text_size: Option<f32>,
+
+    //When set all text is drawn from its glyphs instead of the fonts above and scales with the transform
+    pub sdf_font: Option<SdfFont<T>>,
+    //Only drawn for sdf text, in pixels, a width or offset of 0 draws none
+    pub text_outline_width: f32,
+    pub text_outline_color: Color,
+    pub text_shadow_offset: (f32, f32),
+    pub text_shadow_color: Color,
+
+    pub icons: HashMap<String, T>, //drawn for [icon=name] in rich labels
+
+    pub selection_color: Color,
+    pub error_color: Color, //background of inputs holding something invalid
+
+    pub focus_color: Color, //outline around the widget with keyboard focus
+    pub focus_width: f32, //pixels, 0 draws no outline
+    pub slider_step: f32, //how far the arrow keys move a focused slider
+
+    pub repeat_delay: f32, //seconds a repeating button is held before it clicks again
+    pub repeat_interval: f32, //seconds between the clicks after that
+
+    pub line_spacing: f32,
+
+    pub caret_width: f32,
+    pub caret_blink_rate: f32, //seconds the caret stays on and off, 0 keeps it on
 }
 
 impl GUIStyle {
     pub fn new() -> GUIStyle {
+        GUIStyle::with_empty_texture(TextureRegion::new_invalid())
+    }
+}
+
+impl<T: Clone, F> GUIStyle<T, F> {
+    pub fn with_empty_texture(empty: T) -> GUIStyle<T, F> {
         GUIStyle {
-            foreground_color: Color::from(0x666666FFu32),
-            background_color: Color::from(0xAAAAAAFFu32),
+            button: WidgetStyle::new(empty.clone()),
+            check_box: WidgetStyle::new(empty.clone()),
+            slider: WidgetStyle::new(empty.clone()),
+            text_box: WidgetStyle::new(empty),
+
             text_color: Color::from(0xFFFFFFFF),
+            disabled_text_color: Color::from(0xFFFFFF88u32),
+
+            icons: HashMap::new(),
+
+            selection_color: Color::from(0x3399FF88u32),
+            error_color: Color::from(0xCC4444FFu32),
+
+            focus_color: Color::from(0xFFCC33FFu32),
+            focus_width: 2.0,
+            slider_step: 0.05,
+
+            repeat_delay: 0.5,
+            repeat_interval: 0.1,
 
-            foreground_texture: TextureRegion::new_invalid(),
-            background_texture: TextureRegion::new_invalid(),
+            line_spacing: 1.25,
 
-            check_box_foreground_color: Color::from(0x666666FFu32),
-            check_box_background_color: Color::from(0xAAAAAAFFu32),
+            caret_width: 2.0,
+            caret_blink_rate: 0.5,
 
-            check_box_foreground_texture: TextureRegion::new_invalid(),
-            check_box_background_texture: TextureRegion::new_invalid(),
+            text_align: TextAlign::new(HorizontalAlign::Left, VerticalAlign::Bottom),
+            text_overflow: TextOverflow::Clip,
+            padding: 0.0,
 
-            text_align: TextAlign::LowerLeft,
+            body_font: None,
+            heading_font: None,
+            monospace_font: None,
+            fallback_fonts: Vec::new(),
+            text_size: None,
+
+            sdf_font: None,
+            text_outline_width: 0.0,
+            text_outline_color: Color::from(0x000000FFu32),
+            text_shadow_offset: (0.0, 0.0),
+            text_shadow_color: Color::from(0x00000088u32),
         }
     }
 }
 
-pub struct GUI {
-    pub graphics: Graphics,
-    pub style: GUIStyle,
+//Where a widget in the focus ring is, in frame coordinates, and if it takes left and right itself.
+//Disabled widgets keep their place in the ring so focus stays put when they are enabled, but are skipped
+struct FocusWidget {
+    bounds: (f32, f32, f32, f32),
+    horizontal: bool,
+    disabled: bool,
+}
 
-    key_actions: Vec<Key>,
+//A glyph filled as spans: the atlas image it is in, its cell there, how many pixels it covers
+//across and up as bits of the floats, and the edge the distance field was cut at
+#[derive(PartialEq, Eq, Hash)]
+struct SpanKey {
+    image: usize,
+    cell: (usize, usize, usize, usize),
+    pixels: (u32, u32),
+    edge: u32,
+}
+
+//Column, row and length of a run of covered pixels in a row of a glyph
+type Run = (usize, usize, usize);
+
+pub struct GUI<I: InputSource = WindowInput, R: Canvas = GraphicsCanvas> {
+    pub graphics: R,
+    pub style: GUIStyle<R::Texture, R::Font>,
+    pub input: I,
+    pub clipboard: Box<dyn Clipboard>,
+    pub gamepad: Box<dyn Gamepad>,
 
-    key_listener: bus::BusReader::<(Key, Action)>,
+    key_actions: Vec<Key>,
 
     key_capslock: bool,
     key_shift_left: bool,
     key_shift_right: bool,
-
-    mouse_move_listener : bus::BusReader::<(f32, f32)>,
-    mouse_listener      : bus::BusReader::<(Mouse, Action)>,
+    key_control_left: bool,
+    key_control_right: bool,
+    key_alt_left: bool,
+    key_alt_right: bool,
+    key_super_left: bool,
+    key_super_right: bool,
 
     mouse_x: f32,
     mouse_y: f32,
 
-    mouse_is_pressed: bool,
-    mouse_just_pressed: bool,
+    //indexed by mouse_index
+    mouse_buttons_pressed: [bool; MOUSE_BUTTONS],
+    mouse_buttons_just_pressed: [bool; MOUSE_BUTTONS],
+    mouse_buttons_just_released: [bool; MOUSE_BUTTONS],
+
+    focus: Option<usize>, //the widget with keyboard focus, counted in the order they are drawn
+    focus_widgets: Vec<FocusWidget>, //the widgets drawn so far this frame
+    last_focus_widgets: Vec<FocusWidget>,
+    focus_moved: bool, //the keyboard moved focus, text widgets follow it with their selection
+    focus_visible: bool, //focus came from the keyboard or gamepad, a click focuses without an outline
+
+    gamepad_actions: Vec<GamepadButton>,
 
     text_chars: Vec<char>,
+    preedit: String, //text the input method is composing, shown at the caret until committed
+
+    scroll_x: f32,
+    scroll_y: f32,
+
+    caret_timer: Instant,
+
+    color: Color,
+    text_scaling: (f32, f32), //the scale when the widget being drawn picked its font, sdf text is sized by it
 
-    text_listener: bus::BusReader::<char>,
+    deferred: bool,
+    texture: Option<R::Texture>,
+    canvas_font: Option<R::Font>, //the font set on graphics before a widget switched to one of the style's
+    commands: Vec<DrawCommand<R::Texture, R::Font>>,
+    frame_commands: Vec<DrawCommand<R::Texture, R::Font>>,
+
+    //the runs of the glyphs filled as spans, with the atlas image kept alive so its address isn't reused by another
+    sdf_spans: HashMap<SpanKey, (Rc<Bitmap>, Vec<Run>)>,
 }
 
 impl GUI {
     pub fn new(win: &mut Window) -> GUI {
+        let input = WindowInput::new(win);
+        GUI::with_input(win, input)
+    }
+}
+
+impl<I: InputSource> GUI<I> {
+    pub fn with_input(win: &mut Window, input: I) -> GUI<I> {
+        #[cfg(feature = "embedded-font")]
+        let graphics = {
+            let mut graphics = GraphicsCanvas::new(Graphics::new(win));
+            //if the embedded font can't be written out the engine's font is kept
+            if let Ok(font) = crate::fonts::default_font(crate::fonts::DEFAULT_FONT_SIZE) {
+                graphics.set_font(font);
+            }
+            graphics
+        };
+
+        #[cfg(not(feature = "embedded-font"))]
+        let graphics = GraphicsCanvas::new(Graphics::new(win));
+
+        GUI::with_canvas(graphics, input)
+    }
+}
+
+impl<I: InputSource, R: Canvas> GUI<I, R> {
+    pub fn with_canvas(graphics: R, input: I) -> GUI<I, R> {
         GUI {
-            graphics: Graphics::new(win),
-            style: GUIStyle::new(),
+            graphics,
+            style: GUIStyle::with_empty_texture(R::empty_texture()),
+            input,
+            clipboard: Box::new(MemoryClipboard::new()),
+            gamepad: Box::new(ScriptedGamepad::new()), //nothing is ever pressed on it
 
             key_actions: Vec::new(),
 
-            key_listener: win.create_key_listener(),
-
             key_capslock: false,
             key_shift_left: false,
             key_shift_right: false,
-
-            mouse_move_listener : win.create_mouse_move_listener(),
-            mouse_listener      : win.create_mouse_listener(),
+            key_control_left: false,
+            key_control_right: false,
+            key_alt_left: false,
+            key_alt_right: false,
+            key_super_left: false,
+            key_super_right: false,
 
             mouse_x: 0.0,
             mouse_y: 0.0,
 
-            mouse_is_pressed: false,
-            mouse_just_pressed: false,
+            mouse_buttons_pressed: [false; MOUSE_BUTTONS],
+            mouse_buttons_just_pressed: [false; MOUSE_BUTTONS],
+            mouse_buttons_just_released: [false; MOUSE_BUTTONS],
+
+            focus: None,
+            focus_widgets: Vec::new(),
+            last_focus_widgets: Vec::new(),
+            focus_moved: false,
+            focus_visible: false,
+
+            gamepad_actions: Vec::new(),
 
             text_chars: Vec::new(),
+            preedit: String::new(),
 
-            text_listener: win.create_text_listener(),
+            scroll_x: 0.0,
+            scroll_y: 0.0,
+
+            caret_timer: Instant::now(),
+
+            color: Color::from(0xFFFFFFFFu32),
+            text_scaling: (1.0, 1.0),
+
+            deferred: false,
+            texture: None,
+            canvas_font: None,
+            commands: Vec::new(),
+            frame_commands: Vec::new(),
+
+            sdf_spans: HashMap::new(),
         }
     }
 
-    pub fn button(&mut self, button: &mut Button) {
-        if self.mouse_just_pressed && self.mouse_in_rect(button.bounds()) {
+    //When deferred the widgets only record their draw commands, they are drawn in update
+    pub fn set_deferred(&mut self, deferred: bool) {
+        self.deferred = deferred;
+    }
+
+    pub fn set_clipboard(&mut self, clipboard: Box<dyn Clipboard>) {
+        self.clipboard = clipboard;
+    }
+
+    pub fn set_gamepad(&mut self, gamepad: Box<dyn Gamepad>) {
+        self.gamepad = gamepad;
+    }
+
+    //Button1 is the left mouse button, Button2 the right and Button3 the middle one
+    pub fn mouse_pressed(&self, mouse: Mouse) -> bool {
+        self.mouse_buttons_pressed[mouse_index(mouse)]
+    }
+
+    //Pressed during the last update
+    pub fn mouse_just_pressed(&self, mouse: Mouse) -> bool {
+        self.mouse_buttons_just_pressed[mouse_index(mouse)]
+    }
+
+    //Released during the last update
+    pub fn mouse_just_released(&self, mouse: Mouse) -> bool {
+        self.mouse_buttons_just_released[mouse_index(mouse)]
+    }
+
+    pub fn mouse_position(&self) -> (f32, f32) {
+        (self.mouse_x, self.mouse_y)
+    }
+
+    //Wheel notches scrolled during the last update, positive y scrolls up
+    pub fn scroll(&self) -> (f32, f32) {
+        (self.scroll_x, self.scroll_y)
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            shift: self.key_shift_left || self.key_shift_right,
+            control: self.key_control_left || self.key_control_right,
+            alt: self.key_alt_left || self.key_alt_right,
+            super_key: self.key_super_left || self.key_super_right,
+            caps_lock: self.key_capslock,
+        }
+    }
+
+    //The commands of the last finished frame
+    pub fn draw_commands(&self) -> &[DrawCommand<R::Texture, R::Font>] {
+        &self.frame_commands
+    }
+
+    //The commands recorded so far this frame, can be reordered before update draws them in deferred mode
+    pub fn pending_commands(&mut self) -> &mut Vec<DrawCommand<R::Texture, R::Font>> {
+        &mut self.commands
+    }
+
+    pub fn button(&mut self, button: &mut Button) -> ButtonEvents {
+        self.use_font(button.font);
+        let focused = self.focus_widget(button.bounds(), false, button.disabled);
+
+        let inside = self.mouse_in_rect(button.bounds());
+        button.hovered = inside;
+        let activated = focused && self.activated();
+        let mut events = ButtonEvents::default();
+
+        if !button.disabled && ((self.mouse_just_pressed(Mouse::Button1) && inside) || activated) {
             button.pressed = true;
-        } else if !self.mouse_is_pressed {
+            button.repeated = false;
+            button.next_repeat = repeat_after(self.style.repeat_delay);
+            events.pressed = true;
+        }
+
+        if activated {
+            //a key press is a whole click, the button lets go again next frame
+            events.clicked = true;
+        } else if button.pressed && (button.disabled || !self.mouse_pressed(Mouse::Button1)) {
             button.pressed = false;
+            button.next_repeat = None;
+            events.released = true;
+            //the repeats already were the clicks of this press
+            events.clicked = !button.disabled && inside && !button.repeated && self.mouse_just_released(Mouse::Button1);
         }
 
-        if button.pressed {
-            self.graphics.set_color(self.style.foreground_color);
-            self.graphics.texture(self.style.foreground_texture.clone());
-        } else {
-            self.graphics.set_color(self.style.background_color);
-            self.graphics.texture(self.style.background_texture.clone());
+        //dragged away a repeating button pauses until the mouse comes back
+        if button.repeat && button.pressed && inside && !events.pressed {
+            if let Some(next_repeat) = button.next_repeat {
+                if Instant::now() >= next_repeat {
+                    button.next_repeat = repeat_after(self.style.repeat_interval);
+                    button.repeated = true;
+                    events.clicked = true;
+                }
+            }
         }
+        events.held = button.pressed;
+
+        //dragged away the button lets go if the mouse is released there
+        let state = WidgetState::new(button.disabled, button.pressed && (inside || activated), inside, focused);
+        self.set_state_style(self.style.button.get(state).clone());
 
-        self.graphics.fill_rect(button.x, button.y, button.width, button.height);
+        self.fill_rect(button.x, button.y, button.width, button.height);
 
-        self.graphics.set_color(self.style.text_color);
-        self.draw_text_align(button.x, button.y, button.width, button.height, &button.text);
+        self.set_color(self.widget_text_color(button.disabled));
+        self.draw_text_align(button.bounds(), button.padding, &button.text);
+
+        if focused {
+            self.draw_focus_outline(button.bounds());
+        }
+        events
+    }
+
+    pub fn label(&mut self, label: &mut Label) {
+        label.hovered = self.mouse_in_rect(label.bounds());
+        self.use_font(label.font);
+        self.set_color(self.style.text_color);
+        self.draw_text_align(label.bounds(), label.padding, &label.text);
+    }
+
+    pub fn rich_label(&mut self, label: &mut RichLabel) {
+        label.hovered = self.mouse_in_rect(label.bounds());
+        self.use_font(label.font);
+
+        let spans = markup::parse(&label.text);
+        let content = self.content_box(label.bounds(), label.padding);
+        let max_width = content.2 * self.graphics.frame_width();
+        let wrap = matches!(self.style.text_overflow, TextOverflow::Wrap);
+
+        //every line holds pieces of the spans, the index of the span and the part of its text on that line
+        let mut lines: Vec<Vec<(usize, String)>> = vec![Vec::new()];
+        let mut line_width = 0.0;
+
+        for (i, span) in spans.iter().enumerate() {
+            let text = match span {
+                Span::Text { text, .. } => text.as_str(),
+                Span::Icon(_) => "",
+            };
+
+            for (j, paragraph) in text.split('\n').enumerate() {
+                if j > 0 {
+                    lines.push(Vec::new());
+                    line_width = 0.0;
+                }
+
+                let words = match span {
+                    Span::Text { .. } => paragraph.split_inclusive(' ').collect::<Vec<&str>>(),
+                    Span::Icon(_) => vec![""],
+                };
+
+                for word in words {
+                    let word_width = self.span_width(span, word);
+                    if wrap && line_width > 0.0 && line_width + self.span_width(span, word.trim_end_matches(' ')) > max_width {
+                        if let Some((_, last)) = lines.last_mut().and_then(|line| line.last_mut()) {
+                            last.truncate(last.trim_end_matches(' ').len());
+                        }
+                        lines.push(Vec::new());
+                        line_width = 0.0;
+                    }
+                    line_width += word_width;
+
+                    let line = lines.last_mut().unwrap();
+                    match line.last_mut() {
+                        Some((last, text)) if *last == i => text.push_str(word),
+                        _ => line.push((i, String::from(word))),
+                    }
+                }
+            }
+        }
+
+        let line_h = self.font_height() * self.style.line_spacing / self.graphics.frame_height();
+        let above = self.lines_above(lines.len());
+        let descent = self.font_descent() / self.graphics.frame_height();
+
+        for (i, line) in lines.iter().enumerate() {
+            let width = line.iter().map(|(span, text)| self.span_width(&spans[*span], text)).sum::<f32>();
+            let (mut x, y) = self.line_layout(content, width / self.graphics.frame_width());
+            let y = y + (above - i as f32) * line_h;
+
+            for (span, text) in line {
+                match &spans[*span] {
+                    Span::Text { color, bold, .. } => {
+                        self.set_color(color.unwrap_or(self.style.text_color));
+                        self.draw_clipped(content, text, x, y);
+                        //bold is faked by drawing the text again one pixel to the right
+                        if *bold {
+                            self.draw_clipped(content, text, x + 1.0 / self.graphics.frame_width(), y);
+                        }
+                    }
+                    Span::Icon(name) => {
+                        if let Some(texture) = self.style.icons.get(name).cloned() {
+                            let size = self.font_height();
+                            self.set_color(Color::from(0xFFFFFFFFu32));
+                            self.set_texture(texture);
+                            self.fill_frame_rect(x, y - descent, size / self.graphics.frame_width(), size / self.graphics.frame_height());
+                            self.clear_texture();
+                        }
+                    }
+                }
+                x += self.span_width(&spans[*span], text) / self.graphics.frame_width();
+            }
+        }
+    }
+
+    //Width in pixels of the part text of span, icons are as wide as the font is high
+    fn span_width(&self, span: &Span, text: &str) -> f32 {
+        match span {
+            Span::Text { bold, .. } if *bold && !text.is_empty() => self.text_width(text) + 1.0,
+            Span::Text { .. } => self.text_width(text),
+            Span::Icon(_) => self.font_height(),
+        }
     }
 
     pub fn check_box(&mut self, check_box: &mut CheckBox) {
-        if self.mouse_just_pressed && self.mouse_in_rect(check_box.bounds()) {
-            check_box.pressed = !check_box.pressed;
-        } 
+        let focused = self.focus_widget(check_box.bounds(), false, check_box.disabled);
 
-        if check_box.pressed {
-            self.graphics.set_color(self.style.check_box_foreground_color);
-            self.graphics.texture(self.style.check_box_foreground_texture.clone());
-        } else {
-            self.graphics.set_color(self.style.check_box_background_color);
-            self.graphics.texture(self.style.check_box_background_texture.clone());
+        check_box.hovered = self.mouse_in_rect(check_box.bounds());
+        let clicked = self.mouse_just_pressed(Mouse::Button1) && check_box.hovered;
+        if !check_box.disabled && (clicked || (focused && self.activated())) {
+            check_box.pressed = !check_box.pressed;
         }
 
-        self.graphics.fill_rect(check_box.x, check_box.y, check_box.width, check_box.height);
+        let state = WidgetState::new(check_box.disabled, check_box.pressed, check_box.hovered, focused);
+        self.set_state_style(self.style.check_box.get(state).clone());
+
+        self.fill_rect(check_box.x, check_box.y, check_box.width, check_box.height);
+
+        if focused {
+            self.draw_focus_outline(check_box.bounds());
+        }
     }
 
     pub fn slider(&mut self, slider: &mut Slider) {
@@ -172,114 +671,969 @@ impl GUI {
         let box_x = slider.x+(slider.width-box_w)*slider.val;
         let box_y = slider.y-slider.height*2.0+slider.height/2.0;
 
-        if self.mouse_just_pressed && self.mouse_in_rect((box_x, box_y, box_w, box_h)) {
+        //the knob reaches above and below the bar, a click on either focuses the slider
+        let bottom = slider.y.min(box_y);
+        let top = (slider.y + slider.height).max(box_y + box_h);
+        let bounds = (slider.x, bottom, slider.width, top - bottom);
+
+        let focused = self.focus_widget(bounds, true, slider.disabled);
+
+        slider.hovered = self.mouse_in_rect((box_x, box_y, box_w, box_h));
+        if !slider.disabled && self.mouse_just_pressed(Mouse::Button1) && slider.hovered {
             slider.selected = true;
-        } else if !self.mouse_is_pressed {
+        } else if slider.disabled || !self.mouse_pressed(Mouse::Button1) {
             slider.selected = false;
         }
 
-        self.graphics.texture(self.style.background_texture.clone());
-        self.graphics.set_color(self.style.background_color);
-        self.graphics.fill_rect(slider.x, slider.y, slider.width, slider.height);
+        if focused {
+            for key in &self.key_actions {
+                match key {
+                    Key::Left | Key::Down => slider.val -= self.style.slider_step,
+                    Key::Right | Key::Up => slider.val += self.style.slider_step,
+                    _ => {}
+                }
+            }
+            for button in &self.gamepad_actions {
+                match button {
+                    GamepadButton::Left => slider.val -= self.style.slider_step,
+                    GamepadButton::Right => slider.val += self.style.slider_step,
+                    _ => {}
+                }
+            }
+            slider.val = slider.val.clamp(0.0, 1.0);
+        }
+        let box_x = slider.x+(slider.width-box_w)*slider.val;
+
+        let bar_state = if slider.disabled { WidgetState::Disabled } else { WidgetState::Normal };
+        self.set_state_style(self.style.slider.get(bar_state).clone());
+        self.fill_rect(slider.x, slider.y, slider.width, slider.height);
         
         if slider.selected {
             let g = &self.graphics;
             let mouse_x = (self.mouse_x - g.translation().0) / g.scaling().0;
             slider.val = ((mouse_x-box_w/2.0).max(slider.x).min(slider.x+slider.width-box_w) - slider.x) / (slider.width - box_w);
-            self.graphics.set_color(self.style.foreground_color);
-            self.graphics.texture(self.style.foreground_texture.clone());
-        } else {
-            self.graphics.set_color(self.style.background_color);
-            self.graphics.texture(self.style.background_texture.clone());
         }
+        let state = WidgetState::new(slider.disabled, slider.selected, slider.hovered, focused);
+        self.set_state_style(self.style.slider.get(state).clone());
         
-        self.graphics.fill_rect(box_x, box_y, box_w, box_h);
+        self.fill_rect(box_x, box_y, box_w, box_h);
+
+        if focused {
+            self.draw_focus_outline(bounds);
+        }
     }
 
     pub fn text_box(&mut self, text_box: &mut TextBox) {
-        if self.mouse_just_pressed && self.mouse_in_rect(text_box.bounds()) { 
-            text_box.selected = true;
-        } 
-        
-        if self.mouse_just_pressed && !self.mouse_in_rect(text_box.bounds()) {
-            text_box.selected = false;
-        }
+        self.edit_box(text_box, &|_| true, &|_| true);
+    }
 
-        if text_box.selected {
-            self.graphics.set_color(self.style.foreground_color);
-            self.graphics.texture(self.style.foreground_texture.clone());
+    pub fn number_field(&mut self, field: &mut NumberField) {
+        let was_selected = field.text_box.selected;
 
+        let mut steps = 0.0;
+        if field.text_box.selected {
             for key in &self.key_actions {
-                if *key == Key::Backspace {
-                    text_box.text.pop();
-                } 
+                match key {
+                    Key::Up => steps += 1.0,
+                    Key::Down => steps -= 1.0,
+                    _ => {}
+                }
             }
+        }
+        if !field.text_box.disabled && self.mouse_in_rect(field.bounds()) {
+            steps += self.scroll_y as f64;
+        }
+        if steps != 0.0 {
+            let value = field.parse().unwrap_or(field.value);
+            field.set_value(value + steps * field.step);
+        }
+
+        let key_actions = self.key_actions.clone();
+        self.key_actions.retain(|key| *key != Key::Up && *key != Key::Down);
 
-            for ch in &self.text_chars {
-                text_box.text.push(*ch);
+        let (kind, min, max) = (field.kind, field.min, field.max);
+        self.edit_box(&mut field.text_box, &|ch| kind.accepts(ch), &|text| matches!(kind.parse(text), Some(value) if value >= min && value <= max));
+
+        self.key_actions = key_actions;
+
+        match field.parse() {
+            Some(value) => {
+                field.valid = value >= field.min && value <= field.max;
+                field.value = value.max(field.min).min(field.max);
             }
-        } else {
-            self.graphics.set_color(self.style.background_color);
-            self.graphics.texture(self.style.background_texture.clone());
+            None => field.valid = false,
         }
 
-        self.graphics.fill_rect(text_box.x, text_box.y, text_box.width, text_box.height);
+        //put the clamped value back when the field is left with something invalid in it
+        if was_selected && !field.text_box.selected {
+            field.set_value(field.value);
+        }
+    }
+
+    //Only characters that pass accepts are typed or pasted, boxes whose text isn't valid are drawn in the error color
+    fn edit_box(&mut self, text_box: &mut TextBox, accepts: &dyn Fn(char) -> bool, valid: &dyn Fn(&str) -> bool) {
+        self.use_font(text_box.font);
+        let (bounds, disabled) = (text_box.bounds(), text_box.disabled);
+        let (focused, hovered) = self.text_input(text_box, bounds, disabled, None, accepts, &|gui, text_box| {
+            let display = text_box.display_text();
+            let (text_x, _) = gui.text_box_layout(text_box, &display);
+            text_box.text_index(gui.index_at(&display, 0, display.len(), gui.mouse_x - text_x))
+        });
+        text_box.hovered = hovered;
+
+        if !valid(&text_box.text) {
+            self.set_color(self.style.error_color);
+        }
+
+        self.fill_rect(text_box.x, text_box.y, text_box.width, text_box.height);
+
+        let (box_x, _, box_w, _) = self.content_box(text_box.bounds(), text_box.padding);
+        let box_px = box_w * self.graphics.frame_width();
+        let preedit = self.shown_preedit(text_box.selected, text_box.mask.filter(|_| text_box.is_masked()));
+        let (display, preedit_start, preedit_end) = splice_preedit(&text_box.display_text(), text_box.display_index(text_box.caret), &preedit);
 
-        self.graphics.set_color(self.style.text_color);
+        let text_px = self.text_width(&display);
+        let caret_px = self.text_width(&display[..preedit_end]);
 
-        self.draw_text_align(text_box.x, text_box.y, text_box.width, text_box.height, &text_box.text);
+        if self.caret_moved(text_box.selected, text_box.dragging, &preedit) {
+            if caret_px < text_box.scroll {
+                text_box.scroll = caret_px;
+            } else if caret_px + self.style.caret_width > text_box.scroll + box_px {
+                text_box.scroll = caret_px + self.style.caret_width - box_px;
+            }
+        }
+        let max_scroll = if text_px > box_px { text_px + self.style.caret_width - box_px } else { 0.0 };
+        text_box.scroll = text_box.scroll.min(max_scroll).max(0.0);
+
+        let (text_x, text_y) = self.text_box_layout(text_box, &display);
+        let ascent = self.font_ascent() / self.graphics.frame_height();
+        let descent = self.font_descent() / self.graphics.frame_height();
+
+        if let Some((start, end)) = text_box.selection() {
+            let start = if start == text_box.caret { preedit_end } else { text_box.display_index(start) };
+            let end = if end == text_box.caret { preedit_start } else { text_box.display_index(end) + preedit.len() };
+            let start_x = (text_x + self.text_width(&display[..start]) / self.graphics.frame_width()).max(box_x);
+            let end_x   = (text_x + self.text_width(&display[..end]) / self.graphics.frame_width()).min(box_x + box_w);
+
+            self.set_color(self.style.selection_color);
+            self.clear_texture();
+            self.fill_frame_rect(start_x, text_y - descent, (end_x - start_x).max(0.0), ascent + descent);
+        }
+
+        self.set_color(self.widget_text_color(text_box.disabled));
+
+        let content = self.content_box(text_box.bounds(), text_box.padding);
+        self.draw_clipped(content, &display, text_x, text_y);
+
+        if !preedit.is_empty() {
+            let start_x = (text_x + self.text_width(&display[..preedit_start]) / self.graphics.frame_width()).max(box_x);
+            let end_x = (text_x + caret_px / self.graphics.frame_width()).min(box_x + box_w);
+            self.clear_texture();
+            self.draw_underline(start_x, end_x, text_y);
+        }
+
+        if text_box.selected && self.caret_visible() {
+            let caret_x = text_x + caret_px / self.graphics.frame_width();
+            let caret_w = (self.style.caret_width / self.graphics.frame_width()).min(box_x + box_w - caret_x).max(0.0);
+
+            self.clear_texture();
+            self.fill_frame_rect(caret_x, text_y - descent, caret_w, ascent + descent);
+        }
+
+        if focused {
+            self.draw_focus_outline(text_box.bounds());
+        }
     }
 
-    fn draw_text_align(&mut self, x: f32, y: f32, width: f32, height: f32, text: &str) {
+    pub fn text_area(&mut self, text_area: &mut TextArea) {
+        self.use_font(text_area.font);
+
+        let (x, y, width, height) = self.content_box(text_area.bounds(), text_area.padding);
+        let ascent = self.font_ascent() / self.graphics.frame_height();
+        let descent = self.font_descent() / self.graphics.frame_height();
+        let line_h = self.font_height() / self.graphics.frame_height() * self.style.line_spacing;
+        let top = y + height;
+        let visible_lines = ((height / line_h) as usize).max(1);
+
+        let (bounds, disabled) = (text_area.bounds(), text_area.disabled);
+        let (focused, hovered) = self.text_input(text_area, bounds, disabled, Some(width), &|_| true, &|gui, text_area| {
+            gui.text_area_index_at(text_area, x, width, top, line_h)
+        });
+        text_area.hovered = hovered;
+
+        self.fill_rect(text_area.x, text_area.y, text_area.width, text_area.height);
+
+        let preedit = self.shown_preedit(text_area.selected, None);
+        let (text, preedit_start, preedit_end) = splice_preedit(&text_area.text, text_area.caret, &preedit);
+
+        let lines = self.lines(&text, Some(width));
+        let caret_line = text::line_of(&lines, preedit_end);
+
+        //the wheel scrolls a line per notch, positive y scrolls up towards the start
+        if hovered && !disabled {
+            text_area.scroll = (text_area.scroll as f32 - self.scroll_y.round()).max(0.0) as usize;
+        }
+
+        if self.caret_moved(text_area.selected, text_area.dragging, &preedit) {
+            if caret_line < text_area.scroll {
+                text_area.scroll = caret_line;
+            } else if caret_line >= text_area.scroll + visible_lines {
+                text_area.scroll = caret_line + 1 - visible_lines;
+            }
+        }
+        text_area.scroll = text_area.scroll.min(lines.len().saturating_sub(visible_lines));
+
         let translation = self.graphics.translation();
         let scaling = self.graphics.scaling();
 
-        let mut x = x * scaling.0 + translation.0;
-        let mut y = y * scaling.1 + translation.1;
-        let width  = width  * scaling.0;
-        let height = height * scaling.1;
+        self.graphics.set_translation(0.0, 0.0);
+        self.graphics.set_scale(1.0, 1.0);
 
-        let total_text_width = self.graphics.font().text_width(text) / self.graphics.frame_width() as f32;
-        
-        let chars = text.chars().collect::<Vec<char>>();
-        let mut num_chars = chars.len();
-        let mut text_width = total_text_width;
+        self.push_clip(x, y, width, height);
+        self.clear_texture();
+
+        let selection = text_area.selection().map(|(start, end)| {
+            let start = if start == text_area.caret { preedit_end } else { start };
+            let end = if end == text_area.caret { preedit_start } else { end + preedit.len() };
+            (start, end)
+        });
+
+        //the line cut off at the bottom is left out if the canvas can't clip it
+        let drawn_lines = if self.graphics.can_clip() { visible_lines + 1 } else { visible_lines };
+        for (i, &(start, end)) in lines.iter().enumerate().skip(text_area.scroll).take(drawn_lines) {
+            let line_top = top - (i - text_area.scroll) as f32 * line_h;
+            let baseline = line_top - (line_h - ascent - descent) / 2.0 - ascent;
+
+            if let Some((selection_start, selection_end)) = selection {
+                let from = selection_start.max(start);
+                let to = selection_end.min(end);
+                if from < to || (from == to && selection_start <= start && selection_end > end) {
+                    let from_x = self.text_width(&text[start..from]) / self.graphics.frame_width();
+                    let to_x = self.text_width(&text[start..to]) / self.graphics.frame_width();
+                    self.set_color(self.style.selection_color);
+                    self.fill_rect(x + from_x, line_top - line_h, (to_x - from_x).max(self.style.caret_width / self.graphics.frame_width()), line_h);
+                }
+            }
 
-        while text_width > width {
-            num_chars -= 1;
-            text_width = self.graphics.font().text_width(&chars[..num_chars].iter().collect::<String>()) / self.graphics.frame_width() as f32;
-        } 
+            self.set_color(self.widget_text_color(text_area.disabled));
+            self.draw_string(&text[start..end], x, baseline);
 
-        match self.style.text_align {
-            TextAlign::Center => {
-                x += width / 2.0 - text_width / 2.0;
-                y += height / 2.0 - self.graphics.font().height() as f32 / self.graphics.frame_height() as f32 / 4.0;
+            let from = preedit_start.max(start);
+            let to = preedit_end.min(end);
+            if from < to {
+                let from_x = x + self.text_width(&text[start..from]) / self.graphics.frame_width();
+                let to_x = x + self.text_width(&text[start..to]) / self.graphics.frame_width();
+                self.draw_underline(from_x, to_x, baseline);
             }
-            TextAlign::LowerLeft => {
-                y += self.graphics.font().height() as f32 / self.graphics.frame_height() as f32;
+
+            if i == caret_line && text_area.selected && self.caret_visible() {
+                let caret_x = x + self.text_width(&text[start..preedit_end]) / self.graphics.frame_width();
+                self.fill_rect(caret_x, baseline - descent, self.style.caret_width / self.graphics.frame_width(), ascent + descent);
+            }
+        }
+
+        self.pop_clip();
+
+        self.graphics.set_translation(translation.0, translation.1);
+        self.graphics.set_scale(scaling.0, scaling.1);
+
+        if focused {
+            self.draw_focus_outline(text_area.bounds());
+        }
+    }
+
+    //Mouse, focus and state handling shared by the text widgets, index_at finds the byte index under the mouse.
+    //A click moves the caret and selects the widget, shift extends the selection and dragging moves the caret along.
+    //Returns whether the widget is focused and hovered
+    fn text_input<T: TextEdit>(&mut self, edit: &mut T, bounds: (f32, f32, f32, f32), disabled: bool, wrap_width: Option<f32>,
+            accepts: &dyn Fn(char) -> bool, index_at: &dyn Fn(&Self, &T) -> usize) -> (bool, bool) {
+        let focused = self.focus_widget(bounds, false, disabled);
+        let hovered = self.mouse_in_rect(bounds);
+
+        let parts = edit.edit_parts();
+        let text = parts.text.as_str();
+        *parts.caret = text::clamp_boundary(text, *parts.caret);
+        *parts.selection_anchor = parts.selection_anchor.map(|anchor| text::clamp_boundary(text, anchor));
+
+        let shift = self.key_shift_left || self.key_shift_right;
+
+        if !disabled && self.mouse_just_pressed(Mouse::Button1) && hovered {
+            let caret = index_at(self, edit);
+            let extend = shift && *edit.edit_parts().selected;
+            edit.move_caret(caret, extend);
+            let parts = edit.edit_parts();
+            if parts.selection_anchor.is_none() {
+                *parts.selection_anchor = Some(caret);
+            }
+            *parts.selected = true;
+            *parts.dragging = true;
+            self.caret_timer = Instant::now();
+        } else if !disabled && *edit.edit_parts().dragging && self.mouse_pressed(Mouse::Button1) {
+            let caret = index_at(self, edit);
+            *edit.edit_parts().caret = caret;
+        } else {
+            *edit.edit_parts().dragging = false;
+        }
+
+        let parts = edit.edit_parts();
+        if disabled || (self.mouse_just_pressed(Mouse::Button1) && !hovered) {
+            *parts.selected = false;
+            *parts.selection_anchor = None;
+        }
+
+        if self.focus_moved && *parts.selected != focused {
+            *parts.selected = focused;
+            *parts.selection_anchor = None;
+        }
+
+        let selected = *parts.selected;
+        let state = WidgetState::new(disabled, selected, hovered, focused);
+        self.set_state_style(self.style.text_box.get(state).clone());
+
+        if selected {
+            self.edit_text(edit, wrap_width, accepts);
+        }
+
+        (focused, hovered)
+    }
+
+    //What the input method is composing, shown only in the selected widget and masked like the text
+    fn shown_preedit(&self, selected: bool, mask: Option<char>) -> String {
+        match mask {
+            _ if !selected => String::new(),
+            Some(mask) => (0..text::count(&self.preedit)).map(|_| mask).collect(),
+            None => self.preedit.clone(),
+        }
+    }
+
+    //Whether to keep the caret in view, it only follows the caret after it was moved
+    fn caret_moved(&self, selected: bool, dragging: bool, preedit: &str) -> bool {
+        selected && (!self.key_actions.is_empty() || !self.text_chars.is_empty() || !preedit.is_empty() || dragging)
+    }
+
+    //Applies this frame's keys and typed text to a text widget, wrap_width splits it into lines for multi-line widgets.
+    //Typed and pasted characters that don't pass accepts are dropped
+    fn edit_text<T: TextEdit>(&mut self, edit: &mut T, wrap_width: Option<f32>, accepts: &dyn Fn(char) -> bool) {
+        let shift = self.key_shift_left || self.key_shift_right;
+        let control = self.key_control_left || self.key_control_right;
+
+        //the input method gets the keys while it is composing
+        let keys = if self.preedit.is_empty() { self.key_actions.clone() } else { Vec::new() };
+
+        for key in keys {
+            match key {
+                Key::Backspace => edit.backspace(),
+                Key::Delete    => edit.delete(),
+                Key::Left => {
+                    let to = match edit.selection() {
+                        Some((start, _)) if !shift => start,
+                        _ => text::prev_boundary(edit.text(), edit.caret()),
+                    };
+                    edit.move_caret(to, shift);
+                }
+                Key::Right => {
+                    let to = match edit.selection() {
+                        Some((_, end)) if !shift => end,
+                        _ => text::next_boundary(edit.text(), edit.caret()),
+                    };
+                    edit.move_caret(to, shift);
+                }
+                Key::Home | Key::End | Key::Up | Key::Down => {
+                    let lines = self.lines(edit.text(), wrap_width);
+                    let line = text::line_of(&lines, edit.caret());
+                    let (start, end) = lines[line];
+
+                    let target = match key {
+                        Key::Up => line.checked_sub(1),
+                        Key::Down if line + 1 < lines.len() => Some(line + 1),
+                        _ => None,
+                    };
+
+                    let to = match key {
+                        Key::Home => start,
+                        Key::End if end < edit.text().len() && !edit.text()[end..].starts_with('\n') => text::prev_boundary(edit.text(), end),
+                        Key::End => end,
+                        _ => match target {
+                            Some(target) => {
+                                let x = self.text_width(&edit.text()[start..edit.caret()]) / self.graphics.frame_width();
+                                let (target_start, target_end) = lines[target];
+                                self.index_at(edit.text(), target_start, target_end, x)
+                            }
+                            None if key == Key::Up => 0,
+                            None => edit.text().len(),
+                        }
+                    };
+                    edit.move_caret(to, shift);
+                }
+                Key::Enter if wrap_width.is_some() => edit.insert("\n"),
+                Key::C if control && edit.selection().is_some() && edit.can_copy() => self.clipboard.set(edit.selected_text()),
+                Key::X if control && edit.selection().is_some() && edit.can_copy() => self.clipboard.set(&edit.cut()),
+                Key::V if control => {
+                    if let Some(pasted) = self.clipboard.get() {
+                        let pasted = pasted.chars().filter(|ch| accepts(*ch)).collect::<String>();
+                        if !pasted.is_empty() {
+                            edit.paste(&pasted);
+                        }
+                    }
+                }
+                Key::Z if control && shift => edit.redo(),
+                Key::Z if control => edit.undo(),
+                Key::Y if control => edit.redo(),
+                _ => {}
             }
-            TextAlign::LowerRight => {
-                x += width - text_width;
-                y += height - self.graphics.font().height() as f32 / self.graphics.frame_height() as f32;
+        }
+
+        let typed = self.text_chars.iter().filter(|ch| accepts(**ch)).collect::<String>();
+        if !typed.is_empty() {
+            edit.insert(&typed);
+        }
+
+        if !self.key_actions.is_empty() || !self.text_chars.is_empty() {
+            self.caret_timer = Instant::now();
+        }
+    }
+
+    fn lines(&self, text: &str, wrap_width: Option<f32>) -> Vec<(usize, usize)> {
+        match wrap_width {
+            Some(width) => text::wrap_lines(text, width, |line| self.text_width(line) / self.graphics.frame_width()),
+            None => vec![(0, text.len())],
+        }
+    }
+
+    fn text_area_index_at(&self, text_area: &TextArea, x: f32, width: f32, top: f32, line_h: f32) -> usize {
+        let lines = self.lines(&text_area.text, Some(width));
+        let row = ((top - self.mouse_y) / line_h).max(0.0) as usize;
+        let (start, end) = lines[(text_area.scroll + row).min(lines.len() - 1)];
+        self.index_at(&text_area.text, start, end, self.mouse_x - x)
+    }
+
+    //The part of a widget its text goes in, in frame coordinates
+    fn content_box(&self, bounds: (f32, f32, f32, f32), padding: Option<f32>) -> (f32, f32, f32, f32) {
+        let (x, y, width, height) = self.to_frame(bounds.0, bounds.1, bounds.2, bounds.3);
+        let padding = padding.unwrap_or(self.style.padding);
+        let padding_x = padding / self.graphics.frame_width();
+        let padding_y = padding / self.graphics.frame_height();
+        (x + padding_x, y + padding_y, (width - padding_x * 2.0).max(0.0), (height - padding_y * 2.0).max(0.0))
+    }
+
+    //Where a single line of text starts in its content box in frame coordinates, y is the baseline.
+    //Text wider than the box starts at its left edge
+    fn text_layout(&self, content: (f32, f32, f32, f32), text: &str) -> (f32, f32) {
+        self.line_layout(content, self.text_width(text) / self.graphics.frame_width())
+    }
+
+    //Same as text_layout for a line of text_width in frame units
+    fn line_layout(&self, content: (f32, f32, f32, f32), text_width: f32) -> (f32, f32) {
+        let (x, y, width, height) = content;
+
+        let text_width = text_width.min(width);
+        let ascent = self.font_ascent() / self.graphics.frame_height();
+        let descent = self.font_descent() / self.graphics.frame_height();
+
+        let text_x = match self.style.text_align.horizontal {
+            HorizontalAlign::Left => x,
+            HorizontalAlign::Center => x + (width - text_width) / 2.0,
+            HorizontalAlign::Right => x + width - text_width,
+        };
+
+        let text_y = match self.style.text_align.vertical {
+            VerticalAlign::Top => y + height - ascent,
+            VerticalAlign::Middle => y + (height - ascent + descent) / 2.0,
+            VerticalAlign::Bottom => y + descent,
+            VerticalAlign::Baseline => y,
+        };
+
+        (text_x, text_y)
+    }
+
+    fn text_box_layout(&self, text_box: &TextBox, display: &str) -> (f32, f32) {
+        let (text_x, text_y) = self.text_layout(self.content_box(text_box.bounds(), text_box.padding), display);
+        (text_x - text_box.scroll / self.graphics.frame_width(), text_y)
+    }
+
+    //Draws a label aligned in its box, text wider than the box is handled as the style's text_overflow says
+    fn draw_text_align(&mut self, bounds: (f32, f32, f32, f32), padding: Option<f32>, text: &str) {
+        let content = self.content_box(bounds, padding);
+        let max_width = content.2 * self.graphics.frame_width();
+
+        match self.style.text_overflow {
+            TextOverflow::Clip => {
+                let (text_x, text_y) = self.text_layout(content, text);
+                self.draw_clipped(content, text, text_x, text_y);
             }
-            TextAlign::UpperLeft => {
-                y += height - self.graphics.font().height() as f32 / self.graphics.frame_height() as f32; //TODO add a function that gets the height of the font y += height - font.text_height() as f32 / self.graphics.frame_height() as f32;
+            TextOverflow::Ellipsis | TextOverflow::MiddleEllipsis => {
+                let middle = matches!(self.style.text_overflow, TextOverflow::MiddleEllipsis);
+                let text = text::ellipsize(text, max_width, middle, |part| self.text_width(part));
+                let (text_x, text_y) = self.text_layout(content, &text);
+                self.draw_clipped(content, &text, text_x, text_y);
             }
-            TextAlign::UpperRight => {
-                x += width - text_width;
-                y += height - self.graphics.font().height() as f32 / self.graphics.frame_height() as f32;
+            TextOverflow::Wrap => {
+                let lines = text::wrap_lines(text, max_width, |part| self.text_width(part));
+                let line_h = self.font_height() * self.style.line_spacing / self.graphics.frame_height();
+                let above = self.lines_above(lines.len());
+                let ascent = self.font_ascent() / self.graphics.frame_height();
+                let descent = self.font_descent() / self.graphics.frame_height();
+
+                for (i, &(start, end)) in lines.iter().enumerate() {
+                    let line = text[start..end].trim_end_matches(' ');
+                    let (text_x, text_y) = self.text_layout(content, line);
+                    let line_y = text_y + (above - i as f32) * line_h;
+
+                    //a canvas that can't clip only gets the lines wholly inside the box
+                    let inside = line_y - descent >= content.1 - f32::EPSILON && line_y + ascent <= content.1 + content.3 + f32::EPSILON;
+                    if inside || self.graphics.can_clip() {
+                        self.draw_clipped(content, line, text_x, line_y);
+                    }
+                }
             }
         }
+    }
+
+    //How many lines the first of count lines goes above where a single line would
+    fn lines_above(&self, count: usize) -> f32 {
+        match self.style.text_align.vertical {
+            VerticalAlign::Middle => (count - 1) as f32 / 2.0,
+            VerticalAlign::Bottom | VerticalAlign::Baseline => (count - 1) as f32,
+            VerticalAlign::Top => 0.0,
+        }
+    }
+
+    //Draws text starting at text_x, text_y clipped to the content box, all in frame coordinates
+    fn draw_clipped(&mut self, content: (f32, f32, f32, f32), text: &str, text_x: f32, text_y: f32) {
+        let (x, y, width, height) = content;
+
+        //only the characters reaching into the box are drawn, or the ones wholly inside it if the canvas can't clip
+        let frame_width = self.graphics.frame_width();
+        let hidden = (x - text_x) * frame_width;
+        let (start, end) = if self.graphics.can_clip() {
+            text::visible_range(text, hidden, hidden + width * frame_width, |part| self.text_width(part))
+        } else {
+            text::inside_range(text, hidden, hidden + width * frame_width, |part| self.text_width(part))
+        };
+        let start_x = text_x + self.text_width(&text[..start]) / frame_width;
+
+        let translation = self.graphics.translation();
+        let scaling = self.graphics.scaling();
 
         self.graphics.set_translation(0.0, 0.0);
         self.graphics.set_scale(1.0, 1.0);
 
-        self.graphics.draw_string(&chars[..num_chars].iter().collect::<String>(), x, y);
+        self.push_clip(x, y, width, height);
+        self.draw_string(&text[start..end], start_x, text_y);
+        self.pop_clip();
         
         self.graphics.set_translation(translation.0, translation.1);
         self.graphics.set_scale(scaling.0, scaling.1);
     }
 
+    //Byte index of the character boundary between start and end closest to x, x is measured from where start is drawn
+    fn index_at(&self, text: &str, start: usize, end: usize, x: f32) -> usize {
+        start + text::index_at(&text[start..end], x, |part| self.text_width(part) / self.graphics.frame_width())
+    }
+
+    //A one pixel line under the text between from_x and to_x, in frame coordinates
+    fn draw_underline(&mut self, from_x: f32, to_x: f32, baseline: f32) {
+        let thickness = 1.0 / self.graphics.frame_height();
+        self.fill_frame_rect(from_x, baseline - thickness * 2.0, (to_x - from_x).max(0.0), thickness);
+    }
+
+    fn caret_visible(&self) -> bool {
+        if self.style.caret_blink_rate <= 0.0 {
+            return true;
+        }
+        (self.caret_timer.elapsed().as_secs_f32() / self.style.caret_blink_rate) as u32 & 1 == 0
+    }
+
+    fn to_frame(&self, x: f32, y: f32, width: f32, height: f32) -> (f32, f32, f32, f32) {
+        let translation = self.graphics.translation();
+        let scaling = self.graphics.scaling();
+        (x * scaling.0 + translation.0, y * scaling.1 + translation.1, width * scaling.0, height * scaling.1)
+    }
+
+    //Fills a rect given in frame coordinates no matter what translation and scale are set
+    fn fill_frame_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let translation = self.graphics.translation();
+        let scaling = self.graphics.scaling();
+
+        self.graphics.set_translation(0.0, 0.0);
+        self.graphics.set_scale(1.0, 1.0);
+
+        self.fill_rect(x, y, width, height);
+
+        self.graphics.set_translation(translation.0, translation.1);
+        self.graphics.set_scale(scaling.0, scaling.1);
+    }
+
+    //Counts the widget into the focus ring and tells if it has focus, clicking a widget focuses it.
+    //A widget that takes left and right keeps them from the gamepad while it has focus.
+    //A disabled widget still takes its place in the ring but can't have focus.
+    fn focus_widget(&mut self, bounds: (f32, f32, f32, f32), horizontal: bool, disabled: bool) -> bool {
+        let index = self.focus_widgets.len();
+        let frame_bounds = self.to_frame(bounds.0, bounds.1, bounds.2, bounds.3);
+        self.focus_widgets.push(FocusWidget { bounds: frame_bounds, horizontal, disabled });
+
+        if self.mouse_just_pressed(Mouse::Button1) {
+            if !disabled && self.mouse_in_rect(bounds) {
+                self.focus = Some(index);
+                self.focus_visible = false;
+            } else if self.focus == Some(index) {
+                self.focus = None;
+            }
+        }
+        !disabled && self.focus == Some(index)
+    }
+
+    //Space, Enter or confirm on the gamepad was pressed this frame
+    fn activated(&self) -> bool {
+        self.key_actions.iter().any(|key| *key == Key::Space || *key == Key::Enter) ||
+        self.gamepad_actions.contains(&GamepadButton::Confirm)
+    }
+
+    //Only focus the keyboard or gamepad moved is outlined, the mouse shows where it clicked already
+    fn draw_focus_outline(&mut self, bounds: (f32, f32, f32, f32)) {
+        if !self.focus_visible || self.style.focus_width <= 0.0 {
+            return;
+        }
+
+        let (x, y, width, height) = bounds;
+        let line_w = self.style.focus_width / self.graphics.frame_width() / self.graphics.scaling().0;
+        let line_h = self.style.focus_width / self.graphics.frame_height() / self.graphics.scaling().1;
+
+        self.clear_texture();
+        self.set_color(self.style.focus_color);
+        self.fill_rect(x - line_w, y - line_h, width + 2.0 * line_w, line_h);
+        self.fill_rect(x - line_w, y + height, width + 2.0 * line_w, line_h);
+        self.fill_rect(x - line_w, y, line_w, height);
+        self.fill_rect(x + width, y, line_w, height);
+    }
+
+    //Switches graphics to the style's font for font, one the style doesn't set goes back to the font set on graphics
+    fn use_font(&mut self, font: TextFont) {
+        //the text is drawn after the transform has been reset, so its scale is kept from here
+        self.text_scaling = self.graphics.scaling();
+
+        let body = self.style.body_font.clone();
+        let chosen = match font {
+            TextFont::Body => body,
+            TextFont::Heading => self.style.heading_font.clone().or(body),
+            TextFont::Monospace => self.style.monospace_font.clone().or(body),
+        };
+
+        //with a text size even the canvas font is swapped for a resized one
+        let chosen = match self.style.text_size {
+            Some(size) => {
+                let font = chosen.or_else(|| self.canvas_font.clone()).unwrap_or_else(|| self.graphics.font().clone());
+                Some(font.resized(size * self.text_scaling.1.abs() * self.graphics.frame_height()))
+            }
+            None => chosen,
+        };
+
+        match chosen {
+            Some(chosen) => {
+                if self.canvas_font.is_none() {
+                    self.canvas_font = Some(self.graphics.font().clone());
+                }
+                self.set_font(chosen);
+            }
+            None => {
+                if let Some(original) = self.canvas_font.take() {
+                    self.set_font(original);
+                }
+            }
+        }
+    }
+
+    //Also set when deferred, the widgets measure their text with it
+    fn set_font(&mut self, font: R::Font) {
+        self.commands.push(DrawCommand::Font(font.clone()));
+        self.graphics.set_font(font);
+    }
+
+    //Splits text into runs of characters one font draws, None is the font set on graphics and
+    //Some an index into the fallback fonts. Characters no font has a glyph for stay with graphics.
+    fn font_runs(&self, text: &str) -> Vec<(usize, usize, Option<usize>)> {
+        let mut runs: Vec<(usize, usize, Option<usize>)> = Vec::new();
+
+        for (i, grapheme) in text.grapheme_indices(true) {
+            let ch = grapheme.chars().next().unwrap_or(' ');
+            let font = if self.graphics.font().has_glyph(ch) {
+                None
+            } else {
+                self.style.fallback_fonts.iter().position(|fallback| fallback.has_glyph(ch))
+            };
+
+            match runs.last_mut() {
+                Some((_, end, last)) if *last == font => *end = i + grapheme.len(),
+                _ => runs.push((i, i + grapheme.len(), font)),
+            }
+        }
+        runs
+    }
+
+    //Fallback font i, at the height of the font set on graphics if the style sets a text size
+    fn fallback_font(&self, i: usize) -> R::Font {
+        let font = &self.style.fallback_fonts[i];
+        match self.style.text_size {
+            Some(_) => font.resized(self.graphics.font_height()),
+            None => font.clone(),
+        }
+    }
+
+    //Width of text in pixels with every character measured in the font that draws it
+    fn text_width(&self, text: &str) -> f32 {
+        if let Some(sdf) = &self.style.sdf_font {
+            return sdf.text_width(text) * self.text_scaling.0.abs() * self.graphics.frame_width();
+        }
+        if self.style.fallback_fonts.is_empty() {
+            return self.graphics.text_width(text);
+        }
+
+        self.font_runs(text).iter().map(|(start, end, font)| match font {
+            Some(i) => self.fallback_font(*i).text_width(&text[*start..*end]),
+            None => self.graphics.text_width(&text[*start..*end]),
+        }).sum()
+    }
+
+    fn font_height(&self) -> f32 {
+        match &self.style.sdf_font {
+            Some(sdf) => sdf.size * self.text_scaling.1.abs() * self.graphics.frame_height(),
+            None => self.graphics.font_height(),
+        }
+    }
+
+    fn font_ascent(&self) -> f32 {
+        match &self.style.sdf_font {
+            Some(sdf) => sdf.ascent() * self.text_scaling.1.abs() * self.graphics.frame_height(),
+            None => self.graphics.font_ascent(),
+        }
+    }
+
+    fn font_descent(&self) -> f32 {
+        match &self.style.sdf_font {
+            Some(sdf) => sdf.descent() * self.text_scaling.1.abs() * self.graphics.frame_height(),
+            None => self.graphics.font_descent(),
+        }
+    }
+
+    fn set_color(&mut self, color: Color) {
+        self.color = color;
+        self.commands.push(DrawCommand::Color(color));
+        if !self.deferred {
+            self.graphics.set_color(color);
+        }
+    }
+
+    fn set_texture(&mut self, texture: R::Texture) {
+        if !self.deferred {
+            self.graphics.texture(texture.clone());
+        }
+        self.texture = Some(texture);
+    }
+
+    fn set_state_style(&mut self, style: StateStyle<R::Texture>) {
+        self.set_color(style.color);
+        self.set_texture(style.texture);
+    }
+
+    fn widget_text_color(&self, disabled: bool) -> Color {
+        if disabled { self.style.disabled_text_color } else { self.style.text_color }
+    }
+
+    fn clear_texture(&mut self) {
+        if !self.deferred {
+            self.graphics.texture(R::empty_texture());
+        }
+        self.texture = None;
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let (fx, fy, fw, fh) = self.to_frame(x, y, width, height);
+        self.commands.push(match &self.texture {
+            Some(texture) => DrawCommand::TexturedRect { x: fx, y: fy, width: fw, height: fh, texture: texture.clone() },
+            None => DrawCommand::Rect { x: fx, y: fy, width: fw, height: fh },
+        });
+        if !self.deferred {
+            self.graphics.fill_rect(x, y, width, height);
+        }
+    }
+
+    fn draw_string(&mut self, text: &str, x: f32, y: f32) {
+        if self.style.sdf_font.is_some() {
+            self.draw_sdf_string(text, x, y);
+            return;
+        }
+        if self.style.fallback_fonts.is_empty() {
+            self.draw_run(text, x, y);
+            return;
+        }
+
+        let mut x = x;
+        for (start, end, font) in self.font_runs(text) {
+            let run = &text[start..end];
+            match font {
+                Some(i) => {
+                    let primary = self.graphics.font().clone();
+                    let fallback = self.fallback_font(i);
+                    let width = fallback.text_width(run);
+                    self.set_font(fallback);
+                    self.draw_run(run, x, y);
+                    self.set_font(primary);
+                    x += width / self.graphics.frame_width() / self.graphics.scaling().0;
+                }
+                None => {
+                    self.draw_run(run, x, y);
+                    x += self.graphics.text_width(run) / self.graphics.frame_width() / self.graphics.scaling().0;
+                }
+            }
+        }
+    }
+
+    //Draws text with the sdf font, the shadow and outline go under it in their own passes
+    fn draw_sdf_string(&mut self, text: &str, x: f32, y: f32) {
+        let (x, y, _, _) = self.to_frame(x, y, 0.0, 0.0);
+        let scale = (self.text_scaling.0.abs(), self.text_scaling.1.abs());
+
+        let (quads, distance_per_pixel) = match &self.style.sdf_font {
+            Some(sdf) => (sdf.layout(text, x, y, scale), sdf.distance_per_unit() / (scale.1 * self.graphics.frame_height())),
+            None => return,
+        };
+
+        //about one pixel of blending keeps the edges smooth at any size
+        let softness = distance_per_pixel;
+        let outline = (self.style.text_outline_width * distance_per_pixel).min(0.5 - softness).max(0.0);
+        let fill_color = self.color;
+
+        let (shadow_x, shadow_y) = self.style.text_shadow_offset;
+        if shadow_x != 0.0 || shadow_y != 0.0 {
+            let offset = (shadow_x / self.graphics.frame_width(), shadow_y / self.graphics.frame_height());
+            self.set_color(self.style.text_shadow_color);
+            for quad in &quads {
+                self.fill_sdf_quad(quad, offset, 0.5 - outline, softness);
+            }
+        }
+
+        if outline > 0.0 {
+            self.set_color(self.style.text_outline_color);
+            for quad in &quads {
+                self.fill_sdf_quad(quad, (0.0, 0.0), 0.5 - outline, softness);
+            }
+        }
+
+        self.set_color(fill_color);
+        for quad in &quads {
+            self.fill_sdf_quad(quad, (0.0, 0.0), 0.5, softness);
+        }
+    }
+
+    //Fills a glyph placed in frame coordinates, moved by offset
+    fn fill_sdf_quad(&mut self, quad: &SdfQuad<R::Texture>, offset: (f32, f32), edge: f32, softness: f32) {
+        let rect = (quad.x + offset.0, quad.y + offset.1, quad.width, quad.height);
+        if self.graphics.can_fill_sdf() {
+            self.fill_sdf_frame(quad.texture.clone(), rect, edge, softness);
+        } else {
+            self.fill_sdf_spans(&quad.region, rect, edge);
+        }
+    }
+
+    //For canvases that can't threshold, fills the part of a glyph above edge with plain rects
+    //made from the atlas image, one for every run of covered pixels in a row.
+    //The runs are worked out on the CPU once for every glyph, size and edge and then reused
+    fn fill_sdf_spans(&mut self, region: &ImageRegion, rect: (f32, f32, f32, f32), edge: f32) {
+        let (x, y, width, height) = rect;
+
+        //the frame is 2 units across
+        let pixel_width = 2.0 / self.graphics.frame_width();
+        let pixel_height = 2.0 / self.graphics.frame_height();
+        let (across, up) = (width / pixel_width, height / pixel_height);
+
+        let key = SpanKey {
+            image: Rc::as_ptr(&region.image) as usize,
+            cell: (region.x, region.y, region.width, region.height),
+            pixels: (across.to_bits(), up.to_bits()),
+            edge: edge.to_bits(),
+        };
+        if self.sdf_spans.len() >= MAX_CACHED_GLYPHS && !self.sdf_spans.contains_key(&key) {
+            self.sdf_spans.clear();
+        }
+        let (_, runs) = self.sdf_spans.entry(key).or_insert_with(|| (region.image.clone(), covered_runs(region, across, up, edge)));
+
+        let spans = runs.iter().map(|&(column, row, length)| {
+            (x + column as f32 * pixel_width, y + row as f32 * pixel_height, length as f32 * pixel_width, pixel_height)
+        }).collect::<Vec<(f32, f32, f32, f32)>>();
+
+        for (x, y, width, height) in &spans {
+            self.commands.push(DrawCommand::Rect { x: *x, y: *y, width: *width, height: *height });
+        }
+
+        if !self.deferred {
+            let translation = self.graphics.translation();
+            let scaling = self.graphics.scaling();
+
+            self.graphics.set_translation(0.0, 0.0);
+            self.graphics.set_scale(1.0, 1.0);
+
+            self.graphics.texture(R::empty_texture());
+            for (x, y, width, height) in spans {
+                self.graphics.fill_rect(x, y, width, height);
+            }
+            if let Some(texture) = &self.texture {
+                self.graphics.texture(texture.clone());
+            }
+
+            self.graphics.set_translation(translation.0, translation.1);
+            self.graphics.set_scale(scaling.0, scaling.1);
+        }
+    }
+
+    //Fills a rect given in frame coordinates with a distance field texture
+    fn fill_sdf_frame(&mut self, texture: R::Texture, rect: (f32, f32, f32, f32), edge: f32, softness: f32) {
+        let (x, y, width, height) = rect;
+        self.commands.push(DrawCommand::SdfRect { x, y, width, height, texture: texture.clone(), edge, softness });
+
+        if !self.deferred {
+            let translation = self.graphics.translation();
+            let scaling = self.graphics.scaling();
+
+            self.graphics.set_translation(0.0, 0.0);
+            self.graphics.set_scale(1.0, 1.0);
+
+            self.graphics.texture(texture);
+            self.graphics.fill_sdf(x, y, width, height, edge, softness);
+            if let Some(texture) = &self.texture {
+                self.graphics.texture(texture.clone());
+            } else {
+                self.graphics.texture(R::empty_texture());
+            }
+
+            self.graphics.set_translation(translation.0, translation.1);
+            self.graphics.set_scale(scaling.0, scaling.1);
+        }
+    }
+
+    //Draws text with the font set on graphics
+    fn draw_run(&mut self, text: &str, x: f32, y: f32) {
+        let (fx, fy, _, _) = self.to_frame(x, y, 0.0, 0.0);
+        self.commands.push(DrawCommand::Text { text: String::from(text), x: fx, y: fy });
+        if !self.deferred {
+            self.graphics.draw_string(text, x, y);
+        }
+    }
+
+    fn push_clip(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let (fx, fy, fw, fh) = self.to_frame(x, y, width, height);
+        self.commands.push(DrawCommand::PushClip { x: fx, y: fy, width: fw, height: fh });
+        if !self.deferred {
+            self.graphics.push_clip(x, y, width, height);
+        }
+    }
+
+    fn pop_clip(&mut self) {
+        self.commands.push(DrawCommand::PopClip);
+        if !self.deferred {
+            self.graphics.pop_clip();
+        }
+    }
+
     pub fn clear(&mut self) {
         self.graphics.clear(Color::from((0.0, 0.0, 0.0, 1.0)));
     }
@@ -298,46 +1652,32 @@ impl GUI {
 
     pub fn update(&mut self) {
         //check if the mouse has been moved
-        let mut loop_done = false;
-        while !loop_done {
-            match self.mouse_move_listener.try_recv() {
-                Ok((x, y)) => {
-                    self.mouse_x = x;
-                    self.mouse_y = y;
-                },
-                Err(_) => loop_done = true
-            }
+        while let Some((x, y)) = self.input.poll_mouse_move() {
+            self.mouse_x = x;
+            self.mouse_y = y;
         }
 
 
-        self.mouse_just_pressed = false;
+        self.mouse_buttons_just_pressed = [false; MOUSE_BUTTONS];
+        self.mouse_buttons_just_released = [false; MOUSE_BUTTONS];
 
         //check if any button on the mouse has been pressed or released
-        let mut loop_done = false;
-        while !loop_done {
-            match self.mouse_listener.try_recv() {
-                Ok((mouse, action)) => {
-                    match mouse {
-                        Mouse::Button1 => { //LEFT MOUSE BUTTON
-                            match action {
-                                Action::Press => {
-
-                                    if !self.mouse_is_pressed {
-                                        self.mouse_just_pressed = true;
-                                    }
-
-                                    self.mouse_is_pressed = true;
-                                },
-                                Action::Release => {
-                                    self.mouse_is_pressed = false;
-                                }
-                                _ => {}
-                            }
-                        }
-                        _ => {}
+        while let Some((mouse, action)) = self.input.poll_mouse() {
+            let button = mouse_index(mouse);
+            match action {
+                Action::Press => {
+                    if !self.mouse_buttons_pressed[button] {
+                        self.mouse_buttons_just_pressed[button] = true;
+                    }
+                    self.mouse_buttons_pressed[button] = true;
+                }
+                Action::Release => {
+                    if self.mouse_buttons_pressed[button] {
+                        self.mouse_buttons_just_released[button] = true;
                     }
-                },
-                Err(_) => loop_done = true
+                    self.mouse_buttons_pressed[button] = false;
+                }
+                _ => {}
             }
         }
 
@@ -345,45 +1685,214 @@ impl GUI {
         self.key_actions.clear();
 
         //check if any key has been pressed
-        let mut loop_done = false;
-        while !loop_done {
-            match self.key_listener.try_recv() {
-                Ok((key, action)) => {
-                    match action {
-                        Action::Press => {
-                            if key == Key::LeftShift  { self.key_shift_left     = true; }
-                            if key == Key::RightShift { self.key_shift_right    = true; }
-                            if key == Key::CapsLock   { self.key_capslock      ^= true; }
-
-                            self.key_actions.push(key);
-                        }
-                        Action::Release => {
-                            if key == Key::LeftShift  { self.key_shift_left     = false; }
-                            if key == Key::RightShift { self.key_shift_right    = false; }
-                        }
-                        _ => {}
-                    }
-                },
-                Err(_) => loop_done = true
+        while let Some((key, action)) = self.input.poll_key() {
+            match action {
+                Action::Press => {
+                    if key == Key::LeftShift  { self.key_shift_left     = true; }
+                    if key == Key::RightShift { self.key_shift_right    = true; }
+                    if key == Key::LeftControl  { self.key_control_left   = true; }
+                    if key == Key::RightControl { self.key_control_right  = true; }
+                    if key == Key::LeftAlt    { self.key_alt_left       = true; }
+                    if key == Key::RightAlt   { self.key_alt_right      = true; }
+                    if key == Key::LeftSuper  { self.key_super_left     = true; }
+                    if key == Key::RightSuper { self.key_super_right    = true; }
+                    if key == Key::CapsLock   { self.key_capslock      ^= true; }
+
+                    self.key_actions.push(key);
+                }
+                Action::Release => {
+                    if key == Key::LeftShift  { self.key_shift_left     = false; }
+                    if key == Key::RightShift { self.key_shift_right    = false; }
+                    if key == Key::LeftControl  { self.key_control_left   = false; }
+                    if key == Key::RightControl { self.key_control_right  = false; }
+                    if key == Key::LeftAlt    { self.key_alt_left       = false; }
+                    if key == Key::RightAlt   { self.key_alt_right      = false; }
+                    if key == Key::LeftSuper  { self.key_super_left     = false; }
+                    if key == Key::RightSuper { self.key_super_right    = false; }
+                }
+                _ => { //the key is held down and repeating
+                    self.key_actions.push(key);
+                }
             }
         }
 
 
+        self.scroll_x = 0.0;
+        self.scroll_y = 0.0;
+
+        //check if the mouse wheel has been scrolled
+        while let Some((x, y)) = self.input.poll_scroll() {
+            self.scroll_x += x;
+            self.scroll_y += y;
+        }
+
+        //check if the input method has changed what it is composing
+        while let Some(preedit) = self.input.poll_preedit() {
+            self.preedit = preedit;
+        }
+
+        //Tab and Shift+Tab move focus through the widgets drawn in the frame that just ended
+        self.last_focus_widgets = std::mem::take(&mut self.focus_widgets);
+        let widgets = self.last_focus_widgets.len();
+        let first_enabled = self.last_focus_widgets.iter().position(|widget| !widget.disabled);
+        let tabs = self.key_actions.iter().filter(|key| **key == Key::Tab).count();
+        let backwards = self.key_shift_left || self.key_shift_right;
+
+        self.focus_moved = false;
+        if matches!(self.focus, Some(focus) if focus >= widgets) {
+            self.focus = None;
+        }
+        for _ in 0..tabs {
+            if first_enabled.is_none() {
+                break;
+            }
+            let step = move |focus: usize| if backwards { (focus + widgets - 1) % widgets } else { (focus + 1) % widgets };
+            let mut next = match (self.focus, backwards) {
+                (None, false) => 0,
+                (None, true) => widgets - 1,
+                (Some(focus), _) => step(focus),
+            };
+            while self.last_focus_widgets[next].disabled {
+                next = step(next);
+            }
+            self.focus = Some(next);
+            self.focus_moved = true;
+            self.focus_visible = true;
+        }
+
+        self.gamepad_actions.clear();
+        while let Some(button) = self.gamepad.poll() {
+            self.gamepad_actions.push(button);
+        }
+
+        //gamepad directions move focus to the nearest widget that way, back lets go of it
+        for button in self.gamepad_actions.clone() {
+            match (button, self.focus) {
+                (GamepadButton::Back, _) => {
+                    self.focus = None;
+                    self.focus_moved = true;
+                }
+                (GamepadButton::Confirm, _) => {}
+                (GamepadButton::Left, Some(focus)) | (GamepadButton::Right, Some(focus)) if self.last_focus_widgets[focus].horizontal => {}
+                (_, None) if first_enabled.is_some() => {
+                    self.focus = first_enabled;
+                    self.focus_moved = true;
+                    self.focus_visible = true;
+                }
+                (direction, Some(focus)) => {
+                    if let Some(nearest) = nearest_widget(&self.last_focus_widgets, focus, direction) {
+                        self.focus = Some(nearest);
+                        self.focus_moved = true;
+                    }
+                    self.focus_visible = true;
+                }
+                _ => {}
+            }
+        }
+
         //Make sure char recived from last update is not used again
         self.text_chars.clear();
 
         //check if any char has been recived
-        let mut loop_done = false;
-        while !loop_done {
-            match self.text_listener.try_recv() {
-                Ok(ch) => {
-                    self.text_chars.push(ch);
-                },
-                Err(_) => loop_done = true
-            }
+        while let Some(ch) = self.input.poll_char() {
+            self.text_chars.push(ch);
         }
-        
+
+        //the frame is drawn and the next one starts with the font that was set on graphics
+        let canvas_font = self.canvas_font.take();
+        if let Some(font) = &canvas_font {
+            self.graphics.set_font(font.clone());
+        }
+
+        if self.deferred {
+            replay(&mut self.graphics, &self.commands);
+        }
+
+        if let Some(font) = canvas_font {
+            self.graphics.set_font(font);
+        }
+        self.frame_commands = std::mem::take(&mut self.commands);
+        self.texture = None;
+
         self.graphics.update();
         self.graphics.flush();
     }
+}
+
+//Negative and NaN style values repeat right away, ones too long for an Instant never do
+fn repeat_after(seconds: f32) -> Option<Instant> {
+    let delay = Duration::try_from_secs_f32(seconds.max(0.0)).unwrap_or(Duration::MAX);
+    Instant::now().checked_add(delay)
+}
+
+//The runs of pixels whose distance is at least edge, for a glyph across pixels wide and up pixels high.
+//Rows count up from the bottom
+fn covered_runs(region: &ImageRegion, across: f32, up: f32, edge: f32) -> Vec<Run> {
+    let columns = across.ceil() as usize;
+    let rows = up.ceil() as usize;
+
+    let mut runs = Vec::new();
+    for row in 0..rows {
+        //the atlas image counts rows from the top
+        let v = 1.0 - (row as f32 + 0.5) / up;
+        let mut start = None;
+        for column in 0..=columns {
+            let covered = column < columns && region.sample_alpha((column as f32 + 0.5) / across, v) >= edge;
+            match (covered, start) {
+                (true, None) => start = Some(column),
+                (false, Some(first)) => {
+                    runs.push((first, row, column - first));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    runs
+}
+
+//The text with the composition shown at index, and where the composition starts and ends in it
+fn splice_preedit(text: &str, index: usize, preedit: &str) -> (String, usize, usize) {
+    let mut text = String::from(text);
+    text.insert_str(index, preedit);
+    (text, index, index + preedit.len())
+}
+
+fn mouse_index(mouse: Mouse) -> usize {
+    match mouse {
+        Mouse::Button1 => 0,
+        Mouse::Button2 => 1,
+        Mouse::Button3 => 2,
+        Mouse::Button4 => 3,
+        Mouse::Button5 => 4,
+        Mouse::Button6 => 5,
+        Mouse::Button7 => 6,
+        Mouse::Button8 => 7,
+    }
+}
+
+//The widget whose center is nearest to the center of widget from going in direction,
+//being off to the side counts double so the one straight ahead wins
+fn nearest_widget(widgets: &[FocusWidget], from: usize, direction: GamepadButton) -> Option<usize> {
+    let center = |bounds: (f32, f32, f32, f32)| (bounds.0 + bounds.2 / 2.0, bounds.1 + bounds.3 / 2.0);
+    let (from_x, from_y) = center(widgets[from].bounds);
+
+    widgets.iter().enumerate().filter(|(i, widget)| *i != from && !widget.disabled).filter_map(|(i, widget)| {
+        let (x, y) = center(widget.bounds);
+        let (dx, dy) = (x - from_x, y - from_y);
+
+        let (along, across) = match direction {
+            GamepadButton::Right => (dx, dy),
+            GamepadButton::Left => (-dx, dy),
+            GamepadButton::Up => (dy, dx),
+            GamepadButton::Down => (-dy, dx),
+            _ => return None,
+        };
+
+        if along > 0.0 {
+            Some((i, along + 2.0 * across.abs()))
+        } else {
+            None
+        }
+    }).min_by(|a, b| a.1.total_cmp(&b.1)).map(|(i, _)| i)
 }
\ No newline at end of file
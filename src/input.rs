@@ -0,0 +1,180 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use std::collections::VecDeque;
+
+use engine_core::{window::{Window, Key, Mouse, Action}};
+
+//Where GUI::update pulls its input from, every poll function is drained until it returns None
+pub trait InputSource {
+    fn poll_mouse_move(&mut self) -> Option<(f32, f32)>;
+    fn poll_mouse(&mut self) -> Option<(Mouse, Action)>;
+    fn poll_key(&mut self) -> Option<(Key, Action)>;
+    fn poll_char(&mut self) -> Option<char>;
+    fn poll_scroll(&mut self) -> Option<(f32, f32)>; //wheel notches, positive y scrolls up
+    fn poll_preedit(&mut self) -> Option<String>; //the whole text being composed by an input method, empty when it ends
+}
+
+pub struct WindowInput {
+    key_listener: bus::BusReader::<(Key, Action)>,
+
+    mouse_move_listener : bus::BusReader::<(f32, f32)>,
+    mouse_listener      : bus::BusReader::<(Mouse, Action)>,
+
+    text_listener: bus::BusReader::<char>,
+
+    scroll_listener: Option<bus::BusReader::<(f32, f32)>>,
+    preedit_listener: Option<bus::BusReader::<String>>,
+}
+
+impl WindowInput {
+    pub fn new(win: &mut Window) -> WindowInput {
+        WindowInput::from_listeners(win.create_key_listener(), win.create_mouse_move_listener(), win.create_mouse_listener(), win.create_text_listener())
+    }
+
+    //Listens to buses other than a window's, like ones another event loop broadcasts to
+    pub fn from_listeners(key_listener: bus::BusReader<(Key, Action)>, mouse_move_listener: bus::BusReader<(f32, f32)>,
+            mouse_listener: bus::BusReader<(Mouse, Action)>, text_listener: bus::BusReader<char>) -> WindowInput {
+        WindowInput {
+            key_listener,
+
+            mouse_move_listener,
+            mouse_listener,
+
+            text_listener,
+
+            scroll_listener: None,
+            preedit_listener: None,
+        }
+    }
+
+    //The window doesn't broadcast wheel events itself, whoever receives them hands over a reader for their bus
+    pub fn set_scroll_listener(&mut self, listener: bus::BusReader<(f32, f32)>) {
+        self.scroll_listener = Some(listener);
+    }
+
+    //Nor input method composition, the whole text being composed is broadcast every time it changes and empty when it ends
+    pub fn set_preedit_listener(&mut self, listener: bus::BusReader<String>) {
+        self.preedit_listener = Some(listener);
+    }
+}
+
+impl InputSource for WindowInput {
+    fn poll_mouse_move(&mut self) -> Option<(f32, f32)> {
+        self.mouse_move_listener.try_recv().ok()
+    }
+
+    fn poll_mouse(&mut self) -> Option<(Mouse, Action)> {
+        self.mouse_listener.try_recv().ok()
+    }
+
+    fn poll_key(&mut self) -> Option<(Key, Action)> {
+        self.key_listener.try_recv().ok()
+    }
+
+    fn poll_char(&mut self) -> Option<char> {
+        self.text_listener.try_recv().ok()
+    }
+
+    fn poll_scroll(&mut self) -> Option<(f32, f32)> {
+        self.scroll_listener.as_mut()?.try_recv().ok()
+    }
+
+    //without a preedit listener composed text only shows once committed through the text listener
+    fn poll_preedit(&mut self) -> Option<String> {
+        self.preedit_listener.as_mut()?.try_recv().ok()
+    }
+}
+
+//In memory input that can be filled up by hand, used when there is no window to listen to
+pub struct ScriptedInput {
+    mouse_moves: VecDeque<(f32, f32)>,
+    mouse_actions: VecDeque<(Mouse, Action)>,
+    key_actions: VecDeque<(Key, Action)>,
+    chars: VecDeque<char>,
+    scrolls: VecDeque<(f32, f32)>,
+    preedits: VecDeque<String>,
+}
+
+impl ScriptedInput {
+    pub fn new() -> ScriptedInput {
+        ScriptedInput {
+            mouse_moves: VecDeque::new(),
+            mouse_actions: VecDeque::new(),
+            key_actions: VecDeque::new(),
+            chars: VecDeque::new(),
+            scrolls: VecDeque::new(),
+            preedits: VecDeque::new(),
+        }
+    }
+
+    pub fn move_mouse(&mut self, x: f32, y: f32) {
+        self.mouse_moves.push_back((x, y));
+    }
+
+    pub fn mouse(&mut self, mouse: Mouse, action: Action) {
+        self.mouse_actions.push_back((mouse, action));
+    }
+
+    pub fn key(&mut self, key: Key, action: Action) {
+        self.key_actions.push_back((key, action));
+    }
+
+    pub fn text(&mut self, text: &str) {
+        self.chars.extend(text.chars());
+    }
+
+    pub fn scroll(&mut self, x: f32, y: f32) {
+        self.scrolls.push_back((x, y));
+    }
+
+    pub fn preedit(&mut self, text: &str) {
+        self.preedits.push_back(String::from(text));
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll_mouse_move(&mut self) -> Option<(f32, f32)> {
+        self.mouse_moves.pop_front()
+    }
+
+    fn poll_mouse(&mut self) -> Option<(Mouse, Action)> {
+        self.mouse_actions.pop_front()
+    }
+
+    fn poll_key(&mut self) -> Option<(Key, Action)> {
+        self.key_actions.pop_front()
+    }
+
+    fn poll_char(&mut self) -> Option<char> {
+        self.chars.pop_front()
+    }
+
+    fn poll_scroll(&mut self) -> Option<(f32, f32)> {
+        self.scrolls.pop_front()
+    }
+
+    fn poll_preedit(&mut self) -> Option<String> {
+        self.preedits.pop_front()
+    }
+}
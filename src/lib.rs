@@ -26,17 +26,52 @@ extern crate engine_core;
 
 pub mod gui;
 pub mod comps;
+pub mod clipboard;
+pub mod fonts;
+pub mod gamepad;
+pub mod input;
+pub mod markup;
+pub mod render;
+pub mod raster;
+pub mod sdf;
+pub mod text;
+pub mod ttf;
+
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
 
 #[cfg(test)]
 mod tests {
-    use engine_core::window::{Window, Key};
-    use engine_renderer::{font::Font, renderer::*, texture::{Texture, TextureRegion}};
+    use engine_core::window::{Window, Key, Mouse, Action};
+    use engine_renderer::{renderer::*, texture::{Texture, TextureRegion}};
     use engine_renderer::renderer::std_renderer::*;
     use engine_renderer::color::Color;
-    use crate::comps::{Button, CheckBox, Slider, TextBox};
+    use crate::fonts;
+    use crate::gamepad::{GamepadButton, ScriptedGamepad};
+    use crate::comps::{Button, ButtonEvents, CheckBox, EditHistory, Label, NumberField, NumberKind, RichLabel, Slider, TextArea, TextBox, TextEdit};
     use crate::gui;
+    use crate::input::{InputSource, ScriptedInput, WindowInput};
+    use crate::markup::{self, Span};
+    use crate::raster::{BitmapFont, ImageRegion, SoftwareCanvas};
+    use crate::sdf::{SdfAtlas, SdfFont};
+    use crate::render::{Canvas, DrawCommand};
+    use crate::testing;
+    use crate::text;
+    use crate::ttf::FontFile;
+
+    fn headless_gui() -> gui::GUI<ScriptedInput, SoftwareCanvas> {
+        gui::GUI::with_canvas(SoftwareCanvas::new(64, 64), ScriptedInput::new())
+    }
+
+    fn assert_color(pixel: [u8; 4], color: Color) {
+        let (r, g, b, a) = <(u8, u8, u8, u8)>::from(color);
+        for (p, c) in pixel.iter().zip([r, g, b, a].iter()) {
+            assert!((*p as i32 - *c as i32).abs() <= 1, "{:?} != {:?}", pixel, (r, g, b, a));
+        }
+    }
 
     #[test]
+    #[ignore = "opens a window, needs a display"]
     fn window() {
         let mut win  = Window::new(600, 400, "").unwrap();
         win.make_current();
@@ -54,6 +89,13 @@ mod tests {
             height: 0.5,
             text: String::from("press 1"),
             pressed: false,
+            repeat: false,
+            next_repeat: None,
+            repeated: false,
+            padding: None,
+            font: gui::TextFont::Body,
+            hovered: false,
+            disabled: false,
         };
 
         let mut but_2 = Button {
@@ -63,6 +105,13 @@ mod tests {
             height: 0.5,
             text: String::from("press 2"),
             pressed: false,
+            repeat: false,
+            next_repeat: None,
+            repeated: false,
+            padding: None,
+            font: gui::TextFont::Body,
+            hovered: false,
+            disabled: false,
         };
 
         let mut sli_1 = Slider {
@@ -72,6 +121,8 @@ mod tests {
             height: 0.05,
             val: 0.0,
             selected: false,
+            hovered: false,
+            disabled: false,
         };
 
         let mut text_box = TextBox {
@@ -81,6 +132,17 @@ mod tests {
             height: 0.5,
             text: String::new(),
             selected: false,
+            caret: 0,
+            selection_anchor: None,
+            dragging: false,
+            history: EditHistory::new(100),
+            scroll: 0.0,
+            mask: None,
+            revealed: false,
+            padding: None,
+            font: gui::TextFont::Body,
+            hovered: false,
+            disabled: false,
         };
 
         let mut check_box = CheckBox {
@@ -89,34 +151,39 @@ mod tests {
             width: 0.5,
             height: 0.5,
             pressed: false,
+            hovered: false,
+            disabled: false,
         };
 
         let texture = Texture::from_file("res/textures/tile_sheet.png");
 
-        gui.graphics.set_font(Font::new("res/fonts/arial.ttf", 64));
+        gui.graphics.set_font(fonts::load_font("res/fonts/arial.ttf", 64).unwrap());
 
-        gui.style.foreground_texture = TextureRegion::new(0, texture.height()-16, 16, 16, &texture);
-        gui.style.background_texture = TextureRegion::new(16, texture.height()-16, 16, 16, &texture);
+        let active = TextureRegion::new(0, texture.height()-16, 16, 16, &texture);
+        let normal = TextureRegion::new(16, texture.height()-16, 16, 16, &texture);
+        for widget in [&mut gui.style.button, &mut gui.style.check_box, &mut gui.style.slider, &mut gui.style.text_box] {
+            widget.set_textures(normal.clone(), active.clone());
+        }
 
-        gui.style.text_align = gui::TextAlign::Center;
+        gui.style.text_align = gui::TextAlign::new(gui::HorizontalAlign::Center, gui::VerticalAlign::Middle);
 
         while !win.should_close() {
             gui.clear();
             
-            gui.button(&mut but_1);
-            gui.button(&mut but_2);
+            let clicked_1 = gui.button(&mut but_1).clicked;
+            let clicked_2 = gui.button(&mut but_2).clicked;
             
             gui.slider(&mut sli_1);
 
             gui.check_box(&mut check_box);
 
-            if but_1.pressed {
+            if clicked_1 {
                 println!("button 1 {}", sli_1.val);
                 let (_, g, _, _) = <(u8, u8, u8, u8)>::from(gui.style.text_color);
                 gui.style.text_color = Color::from(((sli_1.val * 255.0) as u8, g, 0, 255));
             }
 
-            if but_2.pressed {
+            if clicked_2 {
                 println!("button 2");
                 let (r, _, _, _) = <(u8, u8, u8, u8)>::from(gui.style.text_color);
                 gui.style.text_color = Color::from((r, (sli_1.val * 255.0) as u8, 0, 255));
@@ -131,6 +198,1262 @@ mod tests {
     }
 
     #[test]
+    fn scripted_input() {
+        let mut input = ScriptedInput::new();
+        input.move_mouse(0.1, 0.2);
+        input.move_mouse(0.3, 0.4);
+        input.key(Key::A, Action::Press);
+        input.text("hi");
+
+        assert_eq!(input.poll_mouse_move(), Some((0.1, 0.2)));
+        assert_eq!(input.poll_mouse_move(), Some((0.3, 0.4)));
+        assert_eq!(input.poll_mouse_move(), None);
+        assert_eq!(input.poll_key(), Some((Key::A, Action::Press)));
+        assert_eq!(input.poll_key(), None);
+        assert_eq!(input.poll_char(), Some('h'));
+        assert_eq!(input.poll_char(), Some('i'));
+        assert_eq!(input.poll_char(), None);
+        assert_eq!(input.poll_mouse(), None);
+
+        //update drains everything queued, nothing is left for the next one
+        let mut gui = headless_gui();
+        gui.input.move_mouse(0.1, 0.2);
+        gui.input.move_mouse(0.3, 0.4);
+        gui.input.scroll(0.0, 1.0);
+        gui.input.scroll(0.0, 2.0);
+        gui.input.mouse(Mouse::Button2, Action::Press);
+        gui.update();
+        assert_eq!(gui.mouse_position(), (0.3, 0.4));
+        assert_eq!(gui.scroll(), (0.0, 3.0));
+        assert!(gui.mouse_just_pressed(Mouse::Button2));
+        assert_eq!(gui.input.poll_mouse_move(), None);
+        assert_eq!(gui.input.poll_scroll(), None);
+
+        gui.update();
+        assert_eq!(gui.scroll(), (0.0, 0.0));
+        assert!(!gui.mouse_just_pressed(Mouse::Button2) && gui.mouse_pressed(Mouse::Button2));
+    }
+
+    #[test]
+    fn software_button() {
+        let mut gui = headless_gui();
+
+        let mut button = Button::new();
+        button.x = -1.0;
+        button.y = -1.0;
+
+        gui.clear();
+        gui.button(&mut button);
+        gui.update();
+        assert_color(gui.graphics.target.pixel(16, 48), gui.style.button.normal.color);
+
+        gui.input.move_mouse(-0.5, -0.5);
+        gui.input.mouse(Mouse::Button1, Action::Press);
+        gui.update();
+
+        gui.clear();
+        gui.button(&mut button);
+        assert!(button.pressed);
+        assert_color(gui.graphics.target.pixel(16, 48), gui.style.button.active.color);
+    }
+
+    #[test]
+    fn recorded_commands() {
+        let mut gui = headless_gui();
+
+        let mut button = Button::new();
+        button.x = -0.5;
+        button.y = -0.5;
+        button.height = 0.5;
+        button.text = String::from("ok");
+
+        gui.button(&mut button);
+        gui.update();
+
+        let commands = gui.draw_commands();
+        assert!(commands.iter().any(|c| matches!(c, DrawCommand::TexturedRect { x, y, width, height, .. }
+            if (*x, *y, *width, *height) == (-0.5, -0.5, 1.0, 0.5))));
+        assert!(commands.iter().any(|c| matches!(c, DrawCommand::Text { text, .. } if text == "ok")));
+        assert!(matches!(commands.last(), Some(DrawCommand::PopClip)));
+    }
+
+    //Set UPDATE_SNAPSHOTS to accept a changed look
+    #[test]
+    fn widget_snapshot() {
+        let mut widgets = testing::Widgets::new();
+        let image = testing::render_frame(gui::GUIStyle::with_empty_texture(None), 128, 128, &mut widgets, |input| {
+            input.move_mouse(0.2, 0.5);
+            input.mouse(Mouse::Button1, Action::Press);
+        });
+
+        assert!(widgets.check_box.pressed);
+        testing::assert_snapshot("res/snapshots/widgets.png", &image, 2);
+
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_none() {
+            let missing = testing::compare_snapshot(std::path::Path::new("res/snapshots/missing.png"), &image, 2);
+            assert!(matches!(missing, Err(testing::SnapshotError::Missing(_))));
+        }
+    }
+
+    #[test]
+    fn text_box_caret() {
+        let mut gui = headless_gui();
+
+        let mut text_box = TextBox::new();
+        text_box.x = -1.0;
+        text_box.y = -1.0;
+        text_box.width = 2.0;
+        text_box.height = 1.0;
+        text_box.text = String::from("helo");
+
+        gui.input.move_mouse(0.99, -0.5);
+        gui.input.mouse(Mouse::Button1, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.caret, 4);
+
+        gui.input.key(Key::Left, Action::Press);
+        gui.input.text("l");
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "hello");
+        assert_eq!(text_box.caret, 4);
+
+        gui.input.key(Key::Home, Action::Press);
+        gui.input.key(Key::Delete, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "ello");
+        assert_eq!(text_box.caret, 0);
+    }
+
+    #[test]
+    fn text_box_selection() {
+        let mut gui = headless_gui();
+
+        let mut text_box = TextBox::new();
+        text_box.text = String::from("hello");
+        text_box.caret = 5;
+        text_box.selected = true;
+
+        gui.input.key(Key::LeftShift, Action::Press);
+        gui.input.key(Key::Left, Action::Press);
+        gui.input.key(Key::Left, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.selected_text(), "lo");
+
+        gui.input.key(Key::LeftShift, Action::Release);
+        gui.input.text("p");
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "help");
+        assert_eq!(text_box.selection(), None);
+    }
+
+    #[test]
+    fn text_box_clipboard() {
+        let mut gui = headless_gui();
+
+        let mut text_box = TextBox::new();
+        text_box.text = String::from("seed 1234");
+        text_box.caret = 9;
+        text_box.selection_anchor = Some(5);
+        text_box.selected = true;
+
+        gui.input.key(Key::LeftControl, Action::Press);
+        gui.input.key(Key::X, Action::Press);
+        gui.input.key(Key::Home, Action::Press);
+        gui.input.key(Key::V, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+
+        assert_eq!(text_box.text, "1234seed ");
+        assert_eq!(gui.clipboard.get(), Some(String::from("1234")));
+    }
+
+    #[test]
+    fn text_box_undo() {
+        let mut gui = headless_gui();
+
+        let mut text_box = TextBox::new();
+        text_box.selected = true;
+
+        gui.input.text("abc");
+        gui.update();
+        gui.text_box(&mut text_box);
+
+        gui.input.text("d");
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "abcd");
+
+        gui.input.key(Key::Backspace, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "abc");
+
+        gui.input.key(Key::LeftControl, Action::Press);
+        gui.input.key(Key::Z, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "abcd");
+
+        gui.input.key(Key::Z, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "");
+
+        gui.input.key(Key::Y, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "abcd");
+    }
+
+    #[test]
+    fn text_area_lines() {
+        assert_eq!(text::wrap_lines("hello world\n\nbye", 6.0, |t| t.len() as f32), vec![(0, 6), (6, 11), (12, 12), (13, 16)]);
+
+        let mut gui = headless_gui();
+
+        let mut text_area = TextArea::new();
+        text_area.x = -1.0;
+        text_area.y = -1.0;
+        text_area.width = 2.0;
+        text_area.height = 2.0;
+        text_area.text = String::from("hello world");
+        text_area.caret = 11;
+        text_area.selected = true;
+
+        gui.input.key(Key::Up, Action::Press);
+        gui.update();
+        gui.text_area(&mut text_area);
+        assert_eq!(text_area.caret, 5);
+
+        gui.input.key(Key::Enter, Action::Press);
+        gui.update();
+        gui.text_area(&mut text_area);
+        assert_eq!(text_area.text, "hello\n world");
+
+        //the wheel scrolls overflowing lines under the mouse, as far as there are lines
+        text_area.text = (1..=9).map(|line| line.to_string()).collect::<Vec<_>>().join("\n");
+        text_area.caret = 0;
+        gui.input.move_mouse(0.0, 0.0);
+        gui.input.scroll(0.0, -2.0);
+        gui.update();
+        gui.text_area(&mut text_area);
+        assert_eq!(text_area.scroll, 2);
+
+        gui.input.scroll(0.0, -20.0);
+        gui.update();
+        gui.text_area(&mut text_area);
+        assert!(text_area.scroll > 2 && text_area.scroll < 9);
+
+        gui.input.scroll(0.0, 20.0);
+        gui.update();
+        gui.text_area(&mut text_area);
+        assert_eq!(text_area.scroll, 0);
+    }
+
+    #[test]
+    fn text_box_scroll() {
+        let mut gui = headless_gui();
+
+        let mut text_box = TextBox::new();
+        text_box.x = -1.0;
+        text_box.y = -1.0;
+        text_box.width = 2.0;
+        text_box.height = 1.0;
+        text_box.selected = true;
+
+        gui.input.text("abcdefgh");
+        gui.update();
+        gui.text_box(&mut text_box);
+        gui.update();
+        assert_eq!(text_box.text, "abcdefgh");
+        assert!(text_box.scroll > 0.0);
+        assert!(gui.draw_commands().iter().any(|command| match command {
+            DrawCommand::Text { text, .. } => text == "cdefgh",
+            _ => false,
+        }));
+
+        gui.input.key(Key::Home, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        gui.update();
+        assert_eq!(text_box.scroll, 0.0);
+        assert!(gui.draw_commands().iter().any(|command| match command {
+            DrawCommand::Text { text, .. } => text == "abcdef",
+            _ => false,
+        }));
+
+        //without clipping only the characters wholly inside the box are drawn
+        gui.graphics.clipping = false;
+        gui.text_box(&mut text_box);
+        gui.update();
+        assert!(gui.draw_commands().iter().any(|command| match command {
+            DrawCommand::Text { text, .. } => text == "abcde",
+            _ => false,
+        }));
+
+        gui.input.key(Key::End, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        gui.update();
+        assert!(gui.draw_commands().iter().any(|command| match command {
+            DrawCommand::Text { text, .. } => text == "defgh",
+            _ => false,
+        }));
+        assert_eq!(text::inside_range("abcd", 5.0, 15.0, |part| part.len() as f32 * 4.0), (2, 3));
+
+        assert_eq!(text::visible_range("abcd", 5.0, 15.0, |part| part.len() as f32 * 4.0), (1, 4));
+    }
+
+    #[test]
+    fn text_box_mask() {
+        let mut gui = headless_gui();
+
+        let mut text_box = TextBox::new();
+        text_box.text = String::from("pässword");
+        text_box.mask = Some('*');
+        text_box.selected = true;
+        assert_eq!(text_box.display_text(), "********");
+        assert_eq!(text_box.display_index(3), 2);
+        assert_eq!(text_box.text_index(2), 3);
+
+        text_box.caret = text_box.text.len();
+        text_box.selection_anchor = Some(0);
+
+        gui.input.key(Key::LeftControl, Action::Press);
+        gui.input.key(Key::C, Action::Press);
+        gui.input.key(Key::X, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        gui.update();
+        assert_eq!(gui.clipboard.get(), None);
+        assert_eq!(text_box.text, "pässword");
+        assert!(gui.draw_commands().iter().all(|command| match command {
+            DrawCommand::Text { text, .. } => !text.contains('p'),
+            _ => true,
+        }));
+
+        text_box.revealed = true;
+        gui.input.key(Key::C, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(gui.clipboard.get(), Some(String::from("pässword")));
+    }
+
+    #[test]
+    fn number_field() {
+        let mut gui = headless_gui();
+
+        let mut field = NumberField::new(NumberKind::Integer);
+        field.min = 0.0;
+        field.max = 10.0;
+        field.text_box.selected = true;
+        field.text_box.caret = 1;
+
+        gui.input.text("5a.");
+        gui.update();
+        gui.number_field(&mut field);
+        assert_eq!(field.text_box.text, "05");
+        assert_eq!(field.value, 5.0);
+        assert!(field.valid);
+
+        gui.input.key(Key::Up, Action::Press);
+        gui.input.key(Key::Up, Action::Press);
+        gui.update();
+        gui.number_field(&mut field);
+        assert_eq!(field.text_box.text, "7");
+        assert_eq!(field.value, 7.0);
+
+        gui.input.text("0");
+        gui.update();
+        gui.number_field(&mut field);
+        assert_eq!(field.text_box.text, "70");
+        assert!(!field.valid);
+        assert_eq!(field.value, 10.0);
+
+        gui.input.move_mouse(-0.5, -0.5);
+        gui.input.mouse(Mouse::Button1, Action::Press);
+        gui.update();
+        gui.number_field(&mut field);
+        assert!(!field.text_box.selected);
+        assert_eq!(field.text_box.text, "10");
+        assert!(field.valid);
+
+        let mut float = NumberField::new(NumberKind::Float);
+        float.step = 0.5;
+        float.text_box.x = -1.0;
+        float.text_box.y = -1.0;
+        float.text_box.width = 2.0;
+        float.text_box.height = 2.0;
+        gui.input.scroll(0.0, -3.0);
+        gui.update();
+        gui.number_field(&mut float);
+        assert_eq!(float.value, -1.5);
+        assert_eq!(float.text_box.text, "-1.50");
+
+        //pasting is filtered like typing
+        field.text_box.selected = true;
+        field.text_box.text = String::new();
+        field.text_box.caret = 0;
+        gui.clipboard.set("7abc");
+        gui.input.key(Key::LeftControl, Action::Press);
+        gui.input.key(Key::V, Action::Press);
+        gui.update();
+        gui.number_field(&mut field);
+        assert_eq!(field.text_box.text, "7");
+        assert_eq!(field.value, 7.0);
+
+        gui.clipboard.set("abc");
+        gui.input.key(Key::V, Action::Press);
+        gui.update();
+        gui.number_field(&mut field);
+        assert_eq!(field.text_box.text, "7");
+    }
+
+    #[test]
+    fn graphemes() {
+        let mut gui = headless_gui();
+
+        let mut text_box = TextBox::new();
+        text_box.text = String::from("ae\u{301}\u{1F1F8}\u{1F1EA}");
+        text_box.caret = text_box.text.len();
+        text_box.selected = true;
+
+        assert_eq!(text::count(&text_box.text), 3);
+        assert_eq!(text::clamp_boundary(&text_box.text, 2), 1);
+
+        gui.input.key(Key::Backspace, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "ae\u{301}");
+
+        gui.input.key(Key::Left, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.caret, 1);
+
+        gui.input.key(Key::Delete, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "a");
+    }
+
+    #[test]
+    fn preedit() {
+        let mut gui = headless_gui();
+
+        let mut text_box = TextBox::new();
+        text_box.text = String::from("ab");
+        text_box.caret = 1;
+        text_box.selected = true;
+
+        gui.input.preedit("ni");
+        gui.input.key(Key::Backspace, Action::Press);
+        gui.update();
+        gui.text_box(&mut text_box);
+        gui.update();
+        assert_eq!(text_box.text, "ab");
+        assert!(gui.draw_commands().iter().any(|command| match command {
+            DrawCommand::Text { text, .. } => text == "anib",
+            _ => false,
+        }));
+
+        gui.input.preedit("");
+        gui.input.text("\u{65E5}");
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "a\u{65E5}b");
+        assert_eq!(text_box.caret, 4);
+    }
+
+    #[test]
+    fn window_input_scroll() {
+        let (mut keys, mut mouse_moves, mut mice, mut chars) = (bus::Bus::new(16), bus::Bus::new(16), bus::Bus::new(16), bus::Bus::new(16));
+        let mut input = WindowInput::from_listeners(keys.add_rx(), mouse_moves.add_rx(), mice.add_rx(), chars.add_rx());
+        assert_eq!(input.poll_scroll(), None);
+
+        let mut scrolls = bus::Bus::new(16);
+        input.set_scroll_listener(scrolls.add_rx());
+        let mut gui = gui::GUI::with_canvas(SoftwareCanvas::new(64, 64), input);
+
+        //every notch broadcast since the last update adds up
+        scrolls.broadcast((0.0, 1.0));
+        scrolls.broadcast((0.5, 2.0));
+        gui.update();
+        assert_eq!(gui.scroll(), (0.5, 3.0));
+
+        gui.update();
+        assert_eq!(gui.scroll(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn window_input_preedit() {
+        let (mut keys, mut mouse_moves, mut mice, mut chars) = (bus::Bus::new(16), bus::Bus::new(16), bus::Bus::new(16), bus::Bus::new(16));
+        let mut input = WindowInput::from_listeners(keys.add_rx(), mouse_moves.add_rx(), mice.add_rx(), chars.add_rx());
+        let mut preedits = bus::Bus::new(16);
+        input.set_preedit_listener(preedits.add_rx());
+        let mut gui = gui::GUI::with_canvas(SoftwareCanvas::new(64, 64), input);
+
+        let mut text_box = TextBox::new();
+        text_box.text = String::from("ab");
+        text_box.caret = 1;
+        text_box.selected = true;
+
+        preedits.broadcast(String::from("ni"));
+        gui.update();
+        gui.text_box(&mut text_box);
+        gui.update();
+        assert!(gui.draw_commands().iter().any(|command| matches!(command, DrawCommand::Text { text, .. } if text == "anib")));
+
+        preedits.broadcast(String::new());
+        chars.broadcast('\u{65E5}');
+        gui.update();
+        gui.text_box(&mut text_box);
+        assert_eq!(text_box.text, "a\u{65E5}b");
+    }
+
+    #[test]
+    fn text_overflow() {
+        let mut gui = headless_gui();
+
+        let mut button = Button::new();
+        button.x = -1.0;
+        button.y = -1.0;
+        button.width = 2.0;
+        button.height = 2.0;
+        button.text = String::from("abcdefgh");
+
+        let mut label = |gui: &mut gui::GUI<ScriptedInput, SoftwareCanvas>, overflow: gui::TextOverflow, text: &str| {
+            gui.style.text_overflow = overflow;
+            button.text = String::from(text);
+            gui.button(&mut button);
+            gui.update();
+            gui.draw_commands().iter().filter_map(|command| match command {
+                DrawCommand::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            }).collect::<Vec<String>>()
+        };
+
+        assert_eq!(label(&mut gui, gui::TextOverflow::Clip, "abcdefgh"), vec!["abcdef"]);
+        assert_eq!(label(&mut gui, gui::TextOverflow::Ellipsis, "abcdefgh"), vec!["abcd\u{2026}"]);
+        assert_eq!(label(&mut gui, gui::TextOverflow::MiddleEllipsis, "abcdefgh"), vec!["ab\u{2026}gh"]);
+        assert_eq!(label(&mut gui, gui::TextOverflow::Ellipsis, "abc"), vec!["abc"]);
+        assert_eq!(label(&mut gui, gui::TextOverflow::Wrap, "abc def gh"), vec!["abc", "def", "gh"]);
+
+        //what sticks out of the button is left out on a canvas that can't clip
+        gui.graphics.clipping = false;
+        assert_eq!(label(&mut gui, gui::TextOverflow::Clip, "abcdefgh"), vec!["abcde"]);
+        assert_eq!(label(&mut gui, gui::TextOverflow::Ellipsis, "abcdefgh"), vec!["abcd\u{2026}"]);
+        assert_eq!(label(&mut gui, gui::TextOverflow::Wrap, "abcd efgh ijkl mnop qrst"), vec!["ijkl", "mnop", "qrst"]);
+        gui.graphics.clipping = true;
+        assert_eq!(label(&mut gui, gui::TextOverflow::Wrap, "abcd efgh ijkl mnop qrst"), vec!["abcd", "efgh", "ijkl", "mnop", "qrst"]);
+
+        let measure = |part: &str| part.len() as f32;
+        assert_eq!(text::fit("abcdef", 3.5, measure), 3);
+        assert_eq!(text::fit_suffix("abcdef", 2.0, measure), 4);
+        assert_eq!(text::index_at("abcdef", 2.4, measure), 2);
+        assert_eq!(text::index_at("abcdef", 2.6, measure), 3);
+        assert_eq!(text::index_at("abcdef", 9.0, measure), 6);
+    }
+
+    #[test]
+    fn text_alignment() {
+        let mut gui = headless_gui();
+
+        let mut button = Button::new();
+        button.x = -1.0;
+        button.y = -1.0;
+        button.width = 2.0;
+        button.height = 1.0;
+        button.text = String::from("ab");
+        button.padding = Some(8.0);
+
+        let mut place = |gui: &mut gui::GUI<ScriptedInput, SoftwareCanvas>, horizontal: gui::HorizontalAlign, vertical: gui::VerticalAlign| {
+            gui.style.text_align = gui::TextAlign::new(horizontal, vertical);
+            gui.button(&mut button);
+            gui.update();
+            gui.draw_commands().iter().find_map(|command| match command {
+                DrawCommand::Text { x, y, .. } => Some((*x, *y)),
+                _ => None,
+            }).unwrap()
+        };
+
+        //the box is 128x64 font pixels with 8 of padding, "ab" is 48 wide and 28 high
+        assert_eq!(place(&mut gui, gui::HorizontalAlign::Left, gui::VerticalAlign::Bottom), (-0.875, -0.875));
+        assert_eq!(place(&mut gui, gui::HorizontalAlign::Right, gui::VerticalAlign::Top), (-0.875 + 1.75 - 0.75, -0.125 - 28.0 / 64.0));
+        assert_eq!(place(&mut gui, gui::HorizontalAlign::Center, gui::VerticalAlign::Middle), (-0.375, -0.5 - 14.0 / 64.0));
+
+        //with 2 of the 7 rows below the baseline the bottom of the text is 8 pixels under it
+        gui.graphics.font.descent = 2;
+        assert_eq!(place(&mut gui, gui::HorizontalAlign::Left, gui::VerticalAlign::Baseline), (-0.875, -0.875));
+        assert_eq!(place(&mut gui, gui::HorizontalAlign::Left, gui::VerticalAlign::Bottom), (-0.875, -0.875 + 8.0 / 64.0));
+        assert_eq!(place(&mut gui, gui::HorizontalAlign::Left, gui::VerticalAlign::Top), (-0.875, -0.125 - 20.0 / 64.0));
+        assert_eq!(place(&mut gui, gui::HorizontalAlign::Left, gui::VerticalAlign::Middle), (-0.875, -0.5 - 6.0 / 64.0));
+    }
+
+    #[test]
+    fn rich_label() {
+        let spans = markup::parse("press [b][color=#FFCC00]E[/color][/b] to [[open] [icon=chest][bad]");
+        assert_eq!(spans.len(), 5);
+        assert!(matches!(&spans[0], Span::Text { text, color: None, bold: false } if text == "press "));
+        assert!(matches!(&spans[1], Span::Text { text, color: Some(_), bold: true } if text == "E"));
+        assert!(matches!(&spans[2], Span::Text { text, color: None, bold: false } if text == " to [open] "));
+        assert!(matches!(&spans[3], Span::Icon(name) if name == "chest"));
+        assert!(matches!(&spans[4], Span::Text { text, .. } if text == "[bad]"));
+
+        let mut gui = headless_gui();
+        gui.style.text_overflow = gui::TextOverflow::Wrap;
+        gui.style.text_align = gui::TextAlign::new(gui::HorizontalAlign::Left, gui::VerticalAlign::Top);
+
+        let mut label = RichLabel::new();
+        label.x = -1.0;
+        label.y = -1.0;
+        label.width = 2.0;
+        label.height = 2.0;
+        label.text = String::from("ab [color=FF0000]cd ef[/color]");
+
+        gui.rich_label(&mut label);
+        gui.update();
+        let texts = gui.draw_commands().iter().filter_map(|command| match command {
+            DrawCommand::Text { text, x, y } => Some((text.clone(), *x, *y)),
+            _ => None,
+        }).collect::<Vec<(String, f32, f32)>>();
+        assert_eq!(texts.len(), 3);
+        assert_eq!((texts[0].0.as_str(), texts[1].0.as_str(), texts[2].0.as_str()), ("ab ", "cd", "ef"));
+        assert_eq!(texts[1].1, texts[0].1 + 72.0 / 64.0);
+        assert_eq!(texts[2].1, texts[0].1);
+        assert!(texts[2].2 < texts[1].2);
+
+        let mut plain = Label::new();
+        plain.x = -1.0;
+        plain.width = 2.0;
+        plain.text = String::from("[b]");
+        gui.label(&mut plain);
+        gui.update();
+        assert!(gui.draw_commands().iter().any(|command| matches!(command, DrawCommand::Text { text, .. } if text == "[b]")));
+    }
+
+    #[test]
+    fn fonts() {
+        let mut gui = headless_gui();
+
+        //half the size of the canvas font and the only one with a lambda
+        let mut symbols = BitmapFont::new(2.0);
+        symbols.insert_glyph('\u{3bb}', [0x41, 0x32, 0x0C, 0x30, 0x40]);
+        gui.style.fallback_fonts.push(symbols);
+        gui.style.heading_font = Some(BitmapFont::new(8.0));
+
+        let mut heading = Label::new();
+        heading.font = gui::TextFont::Heading;
+        heading.text = String::from("a");
+        gui.label(&mut heading);
+
+        let mut body = Label::new();
+        body.x = -1.0;
+        body.width = 2.0;
+        body.text = String::from("a\u{3bb}b");
+        gui.label(&mut body);
+        gui.update();
+
+        let font_scales = |gui: &gui::GUI<ScriptedInput, SoftwareCanvas>| gui.draw_commands().iter().filter_map(|command| match command {
+            DrawCommand::Font(font) => Some(font.scale),
+            _ => None,
+        }).collect::<Vec<f32>>();
+        assert_eq!(font_scales(&gui), vec![8.0, 4.0, 2.0, 4.0]);
+
+        let texts = gui.draw_commands().iter().filter_map(|command| match command {
+            DrawCommand::Text { text, x, .. } => Some((text.clone(), *x)),
+            _ => None,
+        }).collect::<Vec<(String, f32)>>();
+        assert_eq!(texts.len(), 4);
+        assert_eq!(texts[2].0, "\u{3bb}");
+        assert_eq!(texts[2].1, texts[1].1 + 24.0 / 64.0);
+        assert_eq!(texts[3].1, texts[2].1 + 12.0 / 64.0);
+
+        //a widget using a font the style doesn't set gets the canvas font back
+        assert_eq!(gui.graphics.font.scale, 4.0);
+
+        //with a text size the fonts are resized to it, fallbacks too, and follow the transform.
+        //56 pixels is 8 times the height of the glyphs
+        gui.style.text_size = Some(0.875);
+        gui.label(&mut body);
+        gui.update();
+        assert_eq!(font_scales(&gui), vec![8.0, 8.0, 8.0]);
+
+        gui.graphics.set_scale(0.5, 0.5);
+        gui.label(&mut body);
+        gui.update();
+        assert_eq!(font_scales(&gui), vec![4.0, 4.0, 4.0]);
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-font")]
+    fn embedded_font() {
+        use crate::render::FontMetrics;
+
+        let font = fonts::default_font(fonts::DEFAULT_FONT_SIZE).unwrap();
+        assert!(font.text_width("abc") > 0.0);
+        assert!(font.height() > 0.0);
+
+        //the font reaches below the baseline
+        assert!(font.descent() > 0.0);
+        assert!((font.ascent() + font.descent() - font.height()).abs() < 0.001);
+
+        //glyph coverage comes from the file, so fallback fonts are used for what it lacks
+        assert!(font.has_glyph('a') && font.has_glyph('\u{e9}'));
+        assert!(!font.has_glyph('\u{4e00}'));
+
+        //a wrong path gets the embedded font instead of failing
+        let missing = fonts::load_font("res/fonts/missing.ttf", fonts::DEFAULT_FONT_SIZE).unwrap();
+        assert_eq!(missing.text_width("abc"), font.text_width("abc"));
+
+        //the file written the first time is reused
+        assert!(fonts::default_font(fonts::DEFAULT_FONT_SIZE).is_ok());
+    }
+
+    #[test]
+    fn malformed_font_files() {
+        let data = std::fs::read("res/fonts/dejavu_sans_subset.ttf").unwrap();
+        assert!(FontFile::parse(Vec::new()).is_err());
+        assert!(FontFile::parse(data[..100].to_vec()).is_err());
+        assert!(FontFile::parse(vec![0xFF; data.len()]).is_err());
+
+        //the glyph outlines overwritten with junk still parse without panicking, the glyphs just come out broken or empty
+        let tables = u16::from_be_bytes([data[4], data[5]]) as usize;
+        let record = (0..tables).map(|i| 12 + i * 16).find(|record| &data[*record..*record + 4] == b"glyf").unwrap();
+        let offset = u32::from_be_bytes([data[record + 8], data[record + 9], data[record + 10], data[record + 11]]) as usize;
+        let length = u32::from_be_bytes([data[record + 12], data[record + 13], data[record + 14], data[record + 15]]) as usize;
+        let mut junk = data.clone();
+        junk[offset..offset + length].iter_mut().enumerate().for_each(|(i, byte)| *byte = (i * 37 % 251) as u8);
+        let file = FontFile::parse(junk).unwrap();
+        for ch in "ailp\u{e9}".chars() {
+            let glyph = file.glyph_index(ch).unwrap();
+            let _ = file.outline(glyph);
+        }
+        assert!(file.outline(u16::MAX).is_err());
+
+        //a spread of 0 is taken as 1 instead of dividing by it
+        let file = FontFile::parse(data).unwrap();
+        let atlas = SdfAtlas::from_font_file(&file, "l".chars(), 16, 0).unwrap();
+        assert_eq!(atlas.spread, 1);
+        let font = SdfFont::new(&atlas, 0.5, |x, y, width, height| Some(ImageRegion::new(x, y, width, height, &atlas.image)));
+        assert!(font.distance_per_unit().is_finite());
+    }
+
+    #[test]
+    fn sdf_text() {
+        let atlas = SdfAtlas::new(&BitmapFont::new(1.0), 4, 2);
+
+        //the stem of an l is the middle column, the corners of its cell are as far outside as the field reaches
+        let (x, y, _, _) = atlas.glyph_rect('l').unwrap();
+        assert!(atlas.image.pixel(x + 2 + 2 * 4 + 2, y + 2 + 3 * 4)[3] > 128);
+        assert_eq!(atlas.image.pixel(x, y)[3], 0);
+
+        let mut gui = headless_gui();
+        gui.style.sdf_font = Some(SdfFont::new(&atlas, 0.25, |x, y, width, height| Some(ImageRegion::new(x, y, width, height, &atlas.image))));
+
+        let mut label = Label::new();
+        label.x = -0.5;
+        label.y = -0.5;
+        label.height = 0.5;
+        label.text = String::from("ab");
+
+        let quads = |gui: &gui::GUI<ScriptedInput, SoftwareCanvas>| gui.draw_commands().iter().filter_map(|command| match command {
+            DrawCommand::SdfRect { x, height, edge, .. } => Some((*x, *height, *edge)),
+            _ => None,
+        }).collect::<Vec<(f32, f32, f32)>>();
+
+        gui.label(&mut label);
+        gui.update();
+        let plain = quads(&gui);
+        assert_eq!(plain.len(), 2);
+        assert!(gui.graphics.target.pixels.chunks(4).any(|pixel| pixel[3] > 0));
+
+        //the glyphs follow the transform instead of staying at one size
+        gui.graphics.set_scale(2.0, 2.0);
+        gui.label(&mut label);
+        gui.update();
+        assert_eq!(quads(&gui)[0].1, plain[0].1 * 2.0);
+        gui.graphics.set_scale(1.0, 1.0);
+
+        //shadow, then outline, then the text itself
+        gui.style.text_outline_width = 2.0;
+        gui.style.text_shadow_offset = (2.0, -2.0);
+        gui.label(&mut label);
+        gui.update();
+        let styled = quads(&gui);
+        assert_eq!(styled.len(), 6);
+        assert!(styled[0].2 < 0.5 && styled[2].2 < 0.5);
+        assert_eq!(styled[4].2, 0.5);
+        assert_eq!(styled[0].0, styled[2].0 + 2.0 / 64.0);
+
+        //an atlas from a TrueType file has glyphs of their own widths, reaching below the baseline
+        let file = FontFile::open("res/fonts/dejavu_sans_subset.ttf").unwrap();
+        let outlines = SdfAtlas::from_font_file(&file, "ilp ".chars(), 32, 4).unwrap();
+        assert!(outlines.glyph_rect('i').unwrap().2 < outlines.glyph_rect('p').unwrap().2);
+        let (x, y, width, height) = outlines.glyph_rect('l').unwrap();
+        assert!(outlines.image.pixel(x + width / 2, y + height / 2)[3] > 128);
+        assert_eq!(outlines.image.pixel(x, y)[3], 0);
+
+        let outline_font = || SdfFont::new(&outlines, 0.5, |x, y, width, height| Some(ImageRegion::new(x, y, width, height, &outlines.image)));
+        let font = outline_font();
+        assert!(font.descent() > 0.0);
+        assert!(font.layout("p", 0.0, 0.0, (1.0, 1.0))[0].y < -font.descent() / 2.0);
+        assert!(font.text_width("ii") < font.text_width("pp"));
+
+        //a canvas that can't threshold gets the glyphs as plain rects covering about the same pixels
+        label.text = String::from("lip");
+        let mut lit = |thresholding: bool| {
+            let mut gui = headless_gui();
+            gui.graphics.thresholding = thresholding;
+            gui.style.sdf_font = Some(outline_font());
+            gui.label(&mut label);
+            gui.update();
+            let sdf_rects = gui.draw_commands().iter().filter(|command| matches!(command, DrawCommand::SdfRect { .. })).count();
+            (sdf_rects, gui.graphics.target.pixels.chunks(4).filter(|pixel| pixel[3] >= 128).count())
+        };
+        let (sdf_rects, thresholded) = lit(true);
+        let (span_sdf_rects, spans) = lit(false);
+        assert_eq!((sdf_rects, span_sdf_rects), (3, 0));
+        assert!(thresholded > 0);
+        assert!((thresholded as i32 - spans as i32).abs() <= thresholded as i32 / 5, "{} != {}", thresholded, spans);
+
+        //the spans worked out for a glyph are reused in later frames and wherever it is drawn
+        let mut gui = headless_gui();
+        gui.graphics.thresholding = false;
+        gui.style.sdf_font = Some(outline_font());
+        let mut rects = |gui: &mut gui::GUI<ScriptedInput, SoftwareCanvas>| {
+            gui.label(&mut label);
+            gui.update();
+            gui.draw_commands().iter().filter_map(|command| match command {
+                DrawCommand::Rect { x, y, width, height } => Some((*x, *y, *width, *height)),
+                _ => None,
+            }).collect::<Vec<(f32, f32, f32, f32)>>()
+        };
+        let first = rects(&mut gui);
+        assert!(!first.is_empty());
+        assert_eq!(rects(&mut gui), first);
+        gui.graphics.set_translation(0.25, 0.0);
+        let moved = rects(&mut gui);
+        assert_eq!(moved.len(), first.len());
+        assert!(moved.iter().zip(&first).all(|(moved, first)| (moved.0 - first.0 - 0.25).abs() < 1e-4 && moved.2 == first.2));
+    }
+
+    #[test]
+    fn keyboard_focus() {
+        fn frame(gui: &mut gui::GUI<ScriptedInput, SoftwareCanvas>, button: &mut Button, check_box: &mut CheckBox, slider: &mut Slider, text_box: &mut TextBox) {
+            gui.button(button);
+            gui.check_box(check_box);
+            gui.slider(slider);
+            gui.text_box(text_box);
+        }
+
+        let mut gui = headless_gui();
+
+        let mut button = Button::new();
+        button.x = -1.0;
+        button.y = -1.0;
+        button.width = 0.5;
+        button.height = 0.5;
+
+        let mut check_box = CheckBox::new();
+        check_box.x = -0.5;
+        check_box.y = -1.0;
+        check_box.width = 0.5;
+        check_box.height = 0.5;
+
+        let mut slider = Slider::new();
+        slider.x = 0.0;
+        slider.y = -1.0;
+        slider.width = 0.5;
+        slider.height = 0.05;
+
+        let mut text_box = TextBox::new();
+
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+
+        gui.input.key(Key::Tab, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        assert!(!button.pressed);
+
+        gui.input.key(Key::Enter, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        assert!(button.pressed);
+
+        gui.input.key(Key::Tab, Action::Press);
+        gui.input.key(Key::Space, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        assert!(!button.pressed);
+        assert!(check_box.pressed);
+
+        gui.input.key(Key::Tab, Action::Press);
+        gui.input.key(Key::Right, Action::Press);
+        gui.input.key(Key::Right, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        assert!((slider.val - 0.1).abs() < 1e-6);
+
+        gui.input.key(Key::Tab, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        assert!(text_box.selected);
+
+        gui.input.key(Key::LeftShift, Action::Press);
+        gui.input.key(Key::Tab, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        assert!(!text_box.selected);
+
+        //the focused slider is outlined
+        gui.update();
+        let outline = 2.0 / 64.0;
+        let (outline_x, outline_width) = (slider.x - outline, slider.width + 2.0 * outline);
+        let outlined = |gui: &gui::GUI<ScriptedInput, SoftwareCanvas>| gui.draw_commands().iter().any(|command| matches!(command,
+            DrawCommand::Rect { x, width, .. } if *x == outline_x && *width == outline_width));
+        assert!(outlined(&gui));
+
+        //clicking the knob where it reaches above the bar keeps the slider focused, without an outline
+        gui.input.key(Key::LeftShift, Action::Release);
+        gui.input.move_mouse(0.1, -0.9);
+        gui.input.mouse(Mouse::Button1, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        gui.input.mouse(Mouse::Button1, Action::Release);
+        gui.update();
+        assert!(!outlined(&gui));
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        let val = slider.val;
+        gui.input.key(Key::Right, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        assert!((slider.val - val - 0.05).abs() < 1e-6);
+        gui.update();
+        assert!(!outlined(&gui));
+
+        //tabbing back to the slider outlines it again
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        gui.input.key(Key::Tab, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        assert!(text_box.selected);
+        gui.input.key(Key::LeftShift, Action::Press);
+        gui.input.key(Key::Tab, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut check_box, &mut slider, &mut text_box);
+        gui.update();
+        assert!(outlined(&gui));
+    }
+
+    #[test]
+    fn disabled_focus() {
+        fn frame(gui: &mut gui::GUI<ScriptedInput, SoftwareCanvas>, button: &mut Button, text_box: &mut TextBox, check_box: &mut CheckBox) {
+            gui.button(button);
+            gui.text_box(text_box);
+            gui.check_box(check_box);
+        }
+
+        let mut gui = headless_gui();
+
+        let mut button = Button::new();
+        button.x = -1.0;
+        button.y = -1.0;
+        button.width = 0.5;
+        button.height = 0.5;
+        button.disabled = true;
+
+        let mut text_box = TextBox::new();
+        text_box.x = -0.5;
+        text_box.y = -1.0;
+        text_box.width = 0.5;
+        text_box.height = 0.5;
+
+        let mut check_box = CheckBox::new();
+        check_box.x = 0.0;
+        check_box.y = -1.0;
+        check_box.width = 0.5;
+        check_box.height = 0.5;
+
+        frame(&mut gui, &mut button, &mut text_box, &mut check_box);
+
+        //tabbing skips the disabled button
+        gui.input.key(Key::Tab, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut text_box, &mut check_box);
+        assert!(text_box.selected);
+
+        //the button getting enabled while the text box is being typed in doesn't take focus from it
+        button.disabled = false;
+        gui.input.text("a");
+        gui.update();
+        frame(&mut gui, &mut button, &mut text_box, &mut check_box);
+        gui.input.key(Key::Space, Action::Press);
+        gui.input.text(" ");
+        gui.update();
+        let events = gui.button(&mut button);
+        gui.text_box(&mut text_box);
+        gui.check_box(&mut check_box);
+        assert!(!events.pressed);
+        assert_eq!(text_box.text, "a ");
+
+        gui.input.key(Key::Tab, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut text_box, &mut check_box);
+        gui.input.key(Key::Space, Action::Press);
+        gui.update();
+        frame(&mut gui, &mut button, &mut text_box, &mut check_box);
+        assert!(!text_box.selected);
+        assert!(check_box.pressed);
+
+        //the gamepad passes over disabled widgets too
+        let pad = ScriptedGamepad::new();
+        gui.set_gamepad(Box::new(pad.clone()));
+        text_box.disabled = true;
+        gui.update();
+        frame(&mut gui, &mut button, &mut text_box, &mut check_box);
+        pad.press(GamepadButton::Left);
+        gui.update();
+        frame(&mut gui, &mut button, &mut text_box, &mut check_box);
+        pad.press(GamepadButton::Confirm);
+        gui.update();
+        assert!(gui.button(&mut button).clicked);
+    }
+
+    #[test]
+    fn gamepad_navigation() {
+        fn frame(gui: &mut gui::GUI<ScriptedInput, SoftwareCanvas>, buttons: &mut [Button], slider: &mut Slider) {
+            for button in buttons.iter_mut() {
+                gui.button(button);
+            }
+            gui.slider(slider);
+        }
+
+        let mut gui = headless_gui();
+        let pad = ScriptedGamepad::new();
+        gui.set_gamepad(Box::new(pad.clone()));
+
+        //two buttons on top, one below on the left and a slider below on the right
+        let mut buttons = [(-1.0, 0.0), (0.0, 0.0), (-1.0, -0.8)].iter().map(|(x, y)| {
+            let mut button = Button::new();
+            button.x = *x;
+            button.y = *y;
+            button.width = 0.5;
+            button.height = 0.5;
+            button
+        }).collect::<Vec<Button>>();
+
+        let mut slider = Slider::new();
+        slider.x = 0.0;
+        slider.y = -0.6;
+        slider.width = 0.5;
+        slider.height = 0.05;
+
+        let step = |gui: &mut gui::GUI<ScriptedInput, SoftwareCanvas>, presses: &[GamepadButton], buttons: &mut [Button], slider: &mut Slider| {
+            for press in presses {
+                pad.press(*press);
+            }
+            gui.update();
+            frame(gui, buttons, slider);
+        };
+
+        frame(&mut gui, &mut buttons, &mut slider);
+
+        //the first press only picks the first widget
+        step(&mut gui, &[GamepadButton::Right], &mut buttons, &mut slider);
+        step(&mut gui, &[GamepadButton::Right, GamepadButton::Confirm], &mut buttons, &mut slider);
+        assert!(!buttons[0].pressed);
+        assert!(buttons[1].pressed);
+
+        //straight down is the slider, which keeps left and right for itself
+        step(&mut gui, &[GamepadButton::Down, GamepadButton::Right, GamepadButton::Right], &mut buttons, &mut slider);
+        assert!((slider.val - 0.1).abs() < 1e-6);
+        step(&mut gui, &[GamepadButton::Left], &mut buttons, &mut slider);
+        assert!((slider.val - 0.05).abs() < 1e-6);
+
+        step(&mut gui, &[GamepadButton::Up, GamepadButton::Confirm], &mut buttons, &mut slider);
+        assert!(buttons[1].pressed);
+
+        step(&mut gui, &[GamepadButton::Back, GamepadButton::Confirm], &mut buttons, &mut slider);
+        assert!(buttons.iter().all(|button| !button.pressed));
+    }
+
+    #[test]
+    fn mouse_and_modifiers() {
+        let mut gui = headless_gui();
+
+        gui.input.move_mouse(0.5, -0.25);
+        gui.input.mouse(Mouse::Button2, Action::Press);
+        gui.input.mouse(Mouse::Button3, Action::Press);
+        gui.input.mouse(Mouse::Button3, Action::Release);
+        gui.input.scroll(0.0, 2.0);
+        gui.input.scroll(-1.0, 1.0);
+        gui.update();
+        assert_eq!(gui.mouse_position(), (0.5, -0.25));
+        assert!(gui.mouse_pressed(Mouse::Button2) && gui.mouse_just_pressed(Mouse::Button2));
+        assert!(!gui.mouse_pressed(Mouse::Button3) && gui.mouse_just_pressed(Mouse::Button3) && gui.mouse_just_released(Mouse::Button3));
+        assert!(!gui.mouse_pressed(Mouse::Button1));
+        assert_eq!(gui.scroll(), (-1.0, 3.0));
+
+        gui.update();
+        assert!(gui.mouse_pressed(Mouse::Button2) && !gui.mouse_just_pressed(Mouse::Button2));
+        assert_eq!(gui.scroll(), (0.0, 0.0));
+
+        gui.input.mouse(Mouse::Button2, Action::Release);
+        gui.update();
+        assert!(!gui.mouse_pressed(Mouse::Button2) && gui.mouse_just_released(Mouse::Button2));
+
+        gui.input.key(Key::LeftShift, Action::Press);
+        gui.input.key(Key::RightAlt, Action::Press);
+        gui.input.key(Key::LeftSuper, Action::Press);
+        gui.input.key(Key::CapsLock, Action::Press);
+        gui.input.key(Key::CapsLock, Action::Release);
+        gui.input.key(Key::LeftSuper, Action::Release);
+        gui.update();
+        assert_eq!(gui.modifiers(), gui::Modifiers { shift: true, control: false, alt: true, super_key: false, caps_lock: true });
+    }
+
+    #[test]
+    fn button_clicks() {
+        let mut gui = headless_gui();
+
+        let mut button = Button::new();
+        button.x = -1.0;
+        button.y = -1.0;
+
+        gui.input.move_mouse(-0.5, -0.5);
+        gui.input.mouse(Mouse::Button1, Action::Press);
+        gui.update();
+        let events = gui.button(&mut button);
+        assert!(events.pressed && events.held && !events.clicked);
+
+        //dragging away and letting go there is no click
+        gui.input.move_mouse(0.5, 0.5);
+        gui.update();
+        let events = gui.button(&mut button);
+        assert!(!events.pressed && events.held);
+
+        gui.input.mouse(Mouse::Button1, Action::Release);
+        gui.update();
+        let events = gui.button(&mut button);
+        assert!(events.released && !events.held && !events.clicked);
+
+        gui.input.move_mouse(-0.5, -0.5);
+        gui.input.mouse(Mouse::Button1, Action::Press);
+        gui.update();
+        gui.button(&mut button);
+        gui.input.mouse(Mouse::Button1, Action::Release);
+        gui.update();
+        assert_eq!(gui.button(&mut button), ButtonEvents { clicked: true, pressed: false, released: true, held: false });
+
+        //a quick click within one frame
+        gui.input.mouse(Mouse::Button1, Action::Press);
+        gui.input.mouse(Mouse::Button1, Action::Release);
+        gui.update();
+        assert_eq!(gui.button(&mut button), ButtonEvents { clicked: true, pressed: true, released: true, held: false });
+
+        //repeating buttons click every frame once the delay and interval are over
+        button.repeat = true;
+        gui.style.repeat_delay = 0.0;
+        gui.style.repeat_interval = 0.0;
+        gui.input.mouse(Mouse::Button1, Action::Press);
+        gui.update();
+        assert!(!gui.button(&mut button).clicked);
+        for _ in 0..3 {
+            gui.update();
+            assert!(gui.button(&mut button).clicked);
+        }
+
+        //dragged away it stops repeating, and letting go after repeats doesn't click once more
+        gui.input.move_mouse(0.5, 0.5);
+        gui.update();
+        assert!(!gui.button(&mut button).clicked);
+        gui.input.move_mouse(-0.5, -0.5);
+        gui.update();
+        assert!(gui.button(&mut button).clicked);
+        gui.input.mouse(Mouse::Button1, Action::Release);
+        gui.update();
+        assert_eq!(gui.button(&mut button), ButtonEvents { clicked: false, pressed: false, released: true, held: false });
+
+        //nonsense timings don't panic, negative or NaN ones repeat right away and endless ones never
+        for (delay, repeats) in [(-1.0, true), (f32::NAN, true), (f32::INFINITY, false), (1e30, false)] {
+            gui.style.repeat_delay = delay;
+            gui.input.mouse(Mouse::Button1, Action::Press);
+            gui.update();
+            gui.button(&mut button);
+            gui.update();
+            assert_eq!(gui.button(&mut button).clicked, repeats);
+            gui.input.mouse(Mouse::Button1, Action::Release);
+            gui.update();
+            gui.button(&mut button);
+        }
+    }
+
+    #[test]
+    fn widget_states() {
+        let mut gui = headless_gui();
+
+        let mut button = Button::new();
+        button.x = -1.0;
+        button.y = -1.0;
+
+        gui.clear();
+        gui.button(&mut button);
+        assert!(!button.hovered);
+        assert_color(gui.graphics.target.pixel(16, 48), gui.style.button.normal.color);
+
+        gui.input.move_mouse(-0.5, -0.5);
+        gui.update();
+        gui.clear();
+        gui.button(&mut button);
+        assert!(button.hovered);
+        assert_color(gui.graphics.target.pixel(16, 48), gui.style.button.hovered.color);
+
+        //disabled buttons still track hover but ignore the press
+        button.disabled = true;
+        gui.input.mouse(Mouse::Button1, Action::Press);
+        gui.update();
+        gui.clear();
+        assert_eq!(gui.button(&mut button), ButtonEvents::default());
+        assert!(button.hovered && !button.pressed);
+        assert_color(gui.graphics.target.pixel(16, 48), gui.style.button.disabled.color);
+
+        //and are skipped by tab
+        let mut other = Button::new();
+        other.x = 0.0;
+        other.y = -1.0;
+
+        gui.input.mouse(Mouse::Button1, Action::Release);
+        gui.update();
+        gui.button(&mut button);
+        gui.button(&mut other);
+        gui.input.key(Key::Tab, Action::Press);
+        gui.update();
+        gui.button(&mut button);
+        gui.button(&mut other);
+        gui.input.key(Key::Enter, Action::Press);
+        gui.update();
+        gui.button(&mut button);
+        assert!(gui.button(&mut other).clicked);
+
+        let mut check_box = CheckBox::new();
+        check_box.x = -1.0;
+        check_box.y = -1.0;
+        check_box.pressed = true;
+
+        gui.clear();
+        gui.check_box(&mut check_box);
+        assert!(check_box.hovered);
+        assert_color(gui.graphics.target.pixel(16, 48), gui.style.check_box.active.color);
+    }
+
+    #[test]
+    #[ignore = "opens a window, needs a display"]
     fn scaled() {
         let mut win  = Window::new(600, 400, "").unwrap();
         win.make_current();
@@ -148,6 +1471,13 @@ mod tests {
             height: 64.0,
             text: String::from("press 1"),
             pressed: false,
+            repeat: false,
+            next_repeat: None,
+            repeated: false,
+            padding: None,
+            font: gui::TextFont::Body,
+            hovered: false,
+            disabled: false,
         };
 
         let mut but_2 = Button {
@@ -157,6 +1487,13 @@ mod tests {
             height: 64.0,
             text: String::from("press 2"),
             pressed: false,
+            repeat: false,
+            next_repeat: None,
+            repeated: false,
+            padding: None,
+            font: gui::TextFont::Body,
+            hovered: false,
+            disabled: false,
         };
 
         let mut text_box = TextBox {
@@ -166,16 +1503,32 @@ mod tests {
             height: 64.0,
             text: String::new(),
             selected: false,
+            caret: 0,
+            selection_anchor: None,
+            dragging: false,
+            history: EditHistory::new(100),
+            scroll: 0.0,
+            mask: None,
+            revealed: false,
+            padding: None,
+            font: gui::TextFont::Body,
+            hovered: false,
+            disabled: false,
         };
         
         let texture = Texture::from_file("res/textures/tile_sheet.png");
 
-        gui.graphics.set_font(Font::new("res/fonts/arial.ttf", 64));
+        gui.graphics.set_font(fonts::load_font("res/fonts/arial.ttf", 64).unwrap());
+        //the transform below makes a unit one pixel, text 32 pixels high grows and shrinks with it
+        gui.style.text_size = Some(32.0);
 
-        gui.style.foreground_texture = TextureRegion::new(0, texture.height()-16, 16, 16, &texture);
-        gui.style.background_texture = TextureRegion::new(16, texture.height()-16, 16, 16, &texture);
+        let active = TextureRegion::new(0, texture.height()-16, 16, 16, &texture);
+        let normal = TextureRegion::new(16, texture.height()-16, 16, 16, &texture);
+        for widget in [&mut gui.style.button, &mut gui.style.check_box, &mut gui.style.slider, &mut gui.style.text_box] {
+            widget.set_textures(normal.clone(), active.clone());
+        }
 
-        gui.style.text_align = gui::TextAlign::Center;
+        gui.style.text_align = gui::TextAlign::new(gui::HorizontalAlign::Center, gui::VerticalAlign::Middle);
 
         while !win.should_close() {
             gui.clear();
@@ -0,0 +1,106 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use engine_renderer::color::Color;
+
+//A run of text drawn the same way, or an icon from GUIStyle::icons
+#[derive(Clone)]
+pub enum Span {
+    Text { text: String, color: Option<Color>, bold: bool },
+    Icon(String),
+}
+
+//Parses text with inline tags into spans:
+//  [b]bold[/b]  [color=#FF8800]orange[/color]  [icon=sword]  [[ for a literal [
+//Color takes RRGGBB or RRGGBBAA with or without the #. Tags nest, anything that isn't a known tag is kept as text
+pub fn parse(markup: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut text = String::new();
+
+    let mut colors: Vec<Color> = Vec::new();
+    let mut bold: usize = 0;
+
+    let mut rest = markup;
+    while let Some(open) = rest.find('[') {
+        text.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        if rest.starts_with("[[") {
+            text.push('[');
+            rest = &rest[2..];
+            continue;
+        }
+
+        let close = match rest.find(']') {
+            Some(close) => close,
+            None => break,
+        };
+
+        let tag = &rest[1..close];
+        let known = match tag {
+            "b" | "/b" | "/color" => true,
+            _ => (tag.starts_with("color=") && parse_color(&tag[6..]).is_some()) || (tag.starts_with("icon=") && tag.len() > 5),
+        };
+
+        if !known {
+            text.push('[');
+            rest = &rest[1..];
+            continue;
+        }
+
+        push_text(&mut spans, &mut text, colors.last().copied(), bold > 0);
+
+        match tag {
+            "b" => bold += 1,
+            "/b" => bold = bold.saturating_sub(1),
+            "/color" => {
+                colors.pop();
+            }
+            _ if tag.starts_with("color=") => colors.extend(parse_color(&tag[6..])),
+            _ => spans.push(Span::Icon(String::from(&tag[5..]))),
+        }
+
+        rest = &rest[close + 1..];
+    }
+
+    text.push_str(rest);
+    push_text(&mut spans, &mut text, colors.last().copied(), bold > 0);
+
+    spans
+}
+
+fn push_text(spans: &mut Vec<Span>, text: &mut String, color: Option<Color>, bold: bool) {
+    if !text.is_empty() {
+        spans.push(Span::Text { text: std::mem::take(text), color, bold });
+    }
+}
+
+fn parse_color(hex: &str) -> Option<Color> {
+    let hex = hex.trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(Color::from((value << 8) | 0xFF)),
+        8 => Some(Color::from(value)),
+        _ => None,
+    }
+}
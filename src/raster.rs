@@ -0,0 +1,548 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use engine_renderer::color::Color;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::render::{Canvas, FontMetrics};
+use crate::text;
+
+//RGBA pixels stored row by row starting at the top left corner
+#[derive(Clone)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Bitmap {
+    pub fn new(width: usize, height: usize) -> Bitmap {
+        Bitmap {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn from_rgba(width: usize, height: usize, pixels: Vec<u8>) -> Bitmap {
+        assert_eq!(pixels.len(), width * height * 4);
+        Bitmap {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, rgba: [u8; 4]) {
+        let i = (y * self.width + x) * 4;
+        self.pixels[i..i + 4].copy_from_slice(&rgba);
+    }
+
+    fn blend_pixel(&mut self, x: usize, y: usize, src: [f32; 4]) {
+        let dst = self.pixel(x, y);
+        let a = src[3];
+        let mut out = [0u8; 4];
+        for (i, channel) in out.iter_mut().enumerate() {
+            let value = if i == 3 { 1.0 } else { src[i] };
+            *channel = (value * 255.0 * a + dst[i] as f32 * (1.0 - a)).round().clamp(0.0, 255.0) as u8;
+        }
+        self.set_pixel(x, y, out);
+    }
+}
+
+//Part of a bitmap used as a texture, x and y count from the top left corner of the bitmap
+#[derive(Clone)]
+pub struct ImageRegion {
+    pub image: Rc<Bitmap>,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ImageRegion {
+    pub fn new(x: usize, y: usize, width: usize, height: usize, image: &Rc<Bitmap>) -> ImageRegion {
+        ImageRegion {
+            image: image.clone(),
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn whole(image: &Rc<Bitmap>) -> ImageRegion {
+        ImageRegion::new(0, 0, image.width, image.height, image)
+    }
+
+    fn sample(&self, u: f32, v: f32) -> [u8; 4] {
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+        self.image.pixel(self.x + x, self.y + y)
+    }
+
+    //Alpha blended between the four closest pixels, distance fields need it to stay smooth when magnified
+    pub(crate) fn sample_alpha(&self, u: f32, v: f32) -> f32 {
+        let fx = (u * self.width as f32 - 0.5).clamp(0.0, (self.width - 1) as f32);
+        let fy = (v * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (fx as usize, fy as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+
+        let alpha = |x: usize, y: usize| self.image.pixel(self.x + x, self.y + y)[3] as f32 / 255.0;
+        let top = alpha(x0, y0) * (1.0 - tx) + alpha(x1, y0) * tx;
+        let bottom = alpha(x0, y1) * (1.0 - tx) + alpha(x1, y1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 7;
+
+//Glyphs are stored as columns, the lowest bit is the top row
+#[derive(Clone)]
+pub struct BitmapFont {
+    pub scale: f32,
+    pub descent: usize, //rows at the bottom of the glyphs that hang below the baseline
+    glyphs: HashMap<char, [u8; GLYPH_WIDTH]>,
+}
+
+impl BitmapFont {
+    pub fn new(scale: f32) -> BitmapFont {
+        let mut glyphs = HashMap::new();
+        for (i, columns) in ASCII_5X7.iter().enumerate() {
+            glyphs.insert((b' ' + i as u8) as char, *columns);
+        }
+        glyphs.insert('\u{2026}', ELLIPSIS);
+
+        BitmapFont {
+            scale,
+            descent: 0,
+            glyphs,
+        }
+    }
+
+    pub fn insert_glyph(&mut self, ch: char, columns: [u8; GLYPH_WIDTH]) {
+        self.glyphs.insert(ch, columns);
+    }
+
+    pub fn has_glyph(&self, ch: char) -> bool {
+        self.glyphs.contains_key(&ch)
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.glyphs.keys().copied()
+    }
+
+    pub fn glyph(&self, ch: char) -> [u8; GLYPH_WIDTH] {
+        match self.glyphs.get(&ch) {
+            Some(columns) => *columns,
+            None => MISSING_GLYPH,
+        }
+    }
+
+    pub fn advance(&self) -> f32 {
+        (GLYPH_WIDTH + 1) as f32 * self.scale
+    }
+
+    pub fn text_width(&self, text: &str) -> f32 {
+        text::count(text) as f32 * self.advance()
+    }
+
+    pub fn height(&self) -> f32 {
+        GLYPH_HEIGHT as f32 * self.scale
+    }
+}
+
+impl FontMetrics for BitmapFont {
+    fn text_width(&self, text: &str) -> f32 {
+        BitmapFont::text_width(self, text)
+    }
+
+    fn height(&self) -> f32 {
+        BitmapFont::height(self)
+    }
+
+    fn has_glyph(&self, ch: char) -> bool {
+        BitmapFont::has_glyph(self, ch)
+    }
+
+    fn ascent(&self) -> f32 {
+        (GLYPH_HEIGHT - self.descent) as f32 * self.scale
+    }
+
+    fn descent(&self) -> f32 {
+        self.descent as f32 * self.scale
+    }
+
+    fn resized(&self, height: f32) -> BitmapFont {
+        let mut font = self.clone();
+        font.scale = height / GLYPH_HEIGHT as f32;
+        font
+    }
+}
+
+//Draws into a Bitmap on the cpu, one font pixel is 1/frame_width units wide just like Graphics
+pub struct SoftwareCanvas {
+    pub target: Bitmap,
+    pub font: BitmapFont,
+    pub clipping: bool, //false ignores the clip rects, to draw like a canvas that can't clip
+    pub thresholding: bool, //false leaves distance fields to the GUI, to draw like a canvas that can't threshold
+
+    color: Color,
+    texture: Option<ImageRegion>,
+
+    translation: (f32, f32),
+    scaling: (f32, f32),
+
+    //left, top, right and bottom in pixels
+    clips: Vec<(f32, f32, f32, f32)>,
+}
+
+impl SoftwareCanvas {
+    pub fn new(width: usize, height: usize) -> SoftwareCanvas {
+        SoftwareCanvas {
+            target: Bitmap::new(width, height),
+            font: BitmapFont::new(4.0),
+            clipping: true,
+            thresholding: true,
+
+            color: Color::from(0xFFFFFFFFu32),
+            texture: None,
+
+            translation: (0.0, 0.0),
+            scaling: (1.0, 1.0),
+
+            clips: Vec::new(),
+        }
+    }
+
+    fn rgba(&self) -> [f32; 4] {
+        let (r, g, b, a) = <(u8, u8, u8, u8)>::from(self.color);
+        [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0]
+    }
+
+    fn to_pixels(&self, x0: f32, y0: f32, x1: f32, y1: f32) -> (f32, f32, f32, f32) {
+        let w = self.target.width as f32;
+        let h = self.target.height as f32;
+
+        let left   = (x0.min(x1) + 1.0) * 0.5 * w;
+        let right  = (x0.max(x1) + 1.0) * 0.5 * w;
+        let top    = (1.0 - y0.max(y1)) * 0.5 * h;
+        let bottom = (1.0 - y0.min(y1)) * 0.5 * h;
+
+        (left, top, right, bottom)
+    }
+
+    //Fills the rectangle between two corners given in -1..1 frame coordinates, with a distance field
+    //the texture's alpha is thresholded at edge instead of multiplied
+    fn fill_frame_rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, textured: bool, distance_field: Option<(f32, f32)>) {
+        let (left, top, right, bottom) = self.to_pixels(x0, y0, x1, y1);
+
+        if right <= left || bottom <= top {
+            return;
+        }
+
+        let (clip_left, clip_top, clip_right, clip_bottom) = match self.clips.last() {
+            Some(clip) if self.clipping => *clip,
+            _ => (0.0, 0.0, self.target.width as f32, self.target.height as f32),
+        };
+
+        let color = self.rgba();
+
+        let first_x = (left.max(clip_left) - 0.5).ceil().max(0.0) as usize;
+        let first_y = (top.max(clip_top) - 0.5).ceil().max(0.0) as usize;
+
+        for py in first_y..self.target.height {
+            let cy = py as f32 + 0.5;
+            if cy >= bottom || cy >= clip_bottom {
+                break;
+            }
+            for px in first_x..self.target.width {
+                let cx = px as f32 + 0.5;
+                if cx >= right || cx >= clip_right {
+                    break;
+                }
+
+                let mut src = color;
+                if let (Some((edge, softness)), Some(texture)) = (distance_field, &self.texture) {
+                    let distance = texture.sample_alpha((cx - left) / (right - left), (cy - top) / (bottom - top));
+                    let t = ((distance - edge + softness) / (2.0 * softness).max(f32::EPSILON)).clamp(0.0, 1.0);
+                    src[3] *= t * t * (3.0 - 2.0 * t);
+                } else if textured {
+                    if let Some(texture) = &self.texture {
+                        let texel = texture.sample((cx - left) / (right - left), (cy - top) / (bottom - top));
+                        for (channel, texel) in src.iter_mut().zip(texel.iter()) {
+                            *channel *= *texel as f32 / 255.0;
+                        }
+                    }
+                }
+                self.target.blend_pixel(px, py, src);
+            }
+        }
+    }
+}
+
+impl Canvas for SoftwareCanvas {
+    type Texture = Option<ImageRegion>;
+    type Font = BitmapFont;
+
+    fn empty_texture() -> Option<ImageRegion> {
+        None
+    }
+
+    fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    fn texture(&mut self, texture: Option<ImageRegion>) {
+        self.texture = texture;
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let (tx, ty) = self.translation;
+        let (sx, sy) = self.scaling;
+        self.fill_frame_rect(x * sx + tx, y * sy + ty, (x + width) * sx + tx, (y + height) * sy + ty, true, None);
+    }
+
+    fn can_fill_sdf(&self) -> bool {
+        self.thresholding
+    }
+
+    fn fill_sdf(&mut self, x: f32, y: f32, width: f32, height: f32, edge: f32, softness: f32) {
+        let (tx, ty) = self.translation;
+        let (sx, sy) = self.scaling;
+        self.fill_frame_rect(x * sx + tx, y * sy + ty, (x + width) * sx + tx, (y + height) * sy + ty, true, Some((edge, softness)));
+    }
+
+    fn draw_string(&mut self, text: &str, x: f32, y: f32) {
+        let (tx, ty) = self.translation;
+        let (sx, sy) = self.scaling;
+
+        let cell_w = self.font.scale / self.target.width as f32;
+        let cell_h = self.font.scale / self.target.height as f32;
+        let advance = self.font.advance() / self.target.width as f32;
+
+        let mut pen_x = x * sx + tx;
+        let base_y = y * sy + ty;
+
+        //combining marks have no glyphs, every cluster is drawn as its first character
+        for grapheme in text.graphemes(true) {
+            let columns = self.font.glyph(grapheme.chars().next().unwrap_or(' '));
+            for (col, bits) in columns.iter().enumerate() {
+                for row in 0..GLYPH_HEIGHT {
+                    if bits & (1 << row) != 0 {
+                        let x0 = pen_x + col as f32 * cell_w;
+                        let y0 = base_y + ((GLYPH_HEIGHT - 1 - row) as f32 - self.font.descent as f32) * cell_h;
+                        self.fill_frame_rect(x0, y0, x0 + cell_w, y0 + cell_h, false, None);
+                    }
+                }
+            }
+            pen_x += advance;
+        }
+    }
+
+    fn font(&self) -> &BitmapFont {
+        &self.font
+    }
+
+    fn set_font(&mut self, font: BitmapFont) {
+        self.font = font;
+    }
+
+    fn text_width(&self, text: &str) -> f32 {
+        self.font.text_width(text)
+    }
+
+    fn font_height(&self) -> f32 {
+        self.font.height()
+    }
+
+    fn frame_width(&self) -> f32 {
+        self.target.width as f32
+    }
+
+    fn frame_height(&self) -> f32 {
+        self.target.height as f32
+    }
+
+    fn translation(&self) -> (f32, f32) {
+        self.translation
+    }
+
+    fn scaling(&self) -> (f32, f32) {
+        self.scaling
+    }
+
+    fn set_translation(&mut self, x: f32, y: f32) {
+        self.translation = (x, y);
+    }
+
+    fn set_scale(&mut self, x: f32, y: f32) {
+        self.scaling = (x, y);
+    }
+
+    fn can_clip(&self) -> bool {
+        self.clipping
+    }
+
+    fn push_clip(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let (tx, ty) = self.translation;
+        let (sx, sy) = self.scaling;
+        let (mut left, mut top, mut right, mut bottom) = self.to_pixels(x * sx + tx, y * sy + ty, (x + width) * sx + tx, (y + height) * sy + ty);
+
+        if let Some(&(clip_left, clip_top, clip_right, clip_bottom)) = self.clips.last() {
+            left   = left.max(clip_left);
+            top    = top.max(clip_top);
+            right  = right.min(clip_right);
+            bottom = bottom.min(clip_bottom);
+        }
+
+        self.clips.push((left, top, right, bottom));
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    fn clear(&mut self, color: Color) {
+        let (r, g, b, a) = <(u8, u8, u8, u8)>::from(color);
+        for pixel in self.target.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&[r, g, b, a]);
+        }
+    }
+
+    fn flush(&mut self) {}
+}
+
+const MISSING_GLYPH: [u8; GLYPH_WIDTH] = [0x7F, 0x41, 0x41, 0x41, 0x7F];
+
+const ELLIPSIS: [u8; GLYPH_WIDTH] = [0x40, 0x00, 0x40, 0x00, 0x40];
+
+//Printable ascii from ' ' to '~'
+const ASCII_5X7: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
@@ -0,0 +1,257 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use engine_renderer::color::Color;
+use engine_renderer::graphics::Graphics;
+use engine_renderer::texture::TextureRegion;
+
+use crate::fonts::TrueTypeFont;
+use crate::sdf::{SdfAtlas, SdfFont};
+
+//What the GUI needs to know about a font it hasn't set on the canvas yet, measured in pixels
+pub trait FontMetrics {
+    fn text_width(&self, text: &str) -> f32;
+    fn height(&self) -> f32;
+    fn has_glyph(&self, ch: char) -> bool;
+
+    //how far the font reaches above and below the baseline, together they make up height
+    fn ascent(&self) -> f32;
+    fn descent(&self) -> f32;
+
+    //The same font height pixels high, or as close as it comes
+    fn resized(&self, height: f32) -> Self where Self: Sized;
+}
+
+//Everything the GUI needs to draw itself, coordinates follow the same translation/scale rules as Graphics
+pub trait Canvas {
+    type Texture: Clone;
+    type Font: FontMetrics + Clone;
+
+    fn empty_texture() -> Self::Texture;
+
+    fn set_color(&mut self, color: Color);
+    fn texture(&mut self, texture: Self::Texture);
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32);
+
+    //Fills a rect with the texture's alpha read as a signed distance field, covered where it's above edge
+    //and blended over softness on both sides. A canvas that can't threshold says so in can_fill_sdf
+    //and the GUI fills the glyphs from the atlas image itself.
+    fn can_fill_sdf(&self) -> bool;
+    fn fill_sdf(&mut self, x: f32, y: f32, width: f32, height: f32, edge: f32, softness: f32);
+    fn draw_string(&mut self, text: &str, x: f32, y: f32);
+
+    fn font(&self) -> &Self::Font;
+    fn set_font(&mut self, font: Self::Font);
+
+    //measured in pixels of the current font
+    fn text_width(&self, text: &str) -> f32;
+    fn font_height(&self) -> f32;
+
+    fn font_ascent(&self) -> f32 {
+        self.font().ascent()
+    }
+
+    fn font_descent(&self) -> f32 {
+        self.font().descent()
+    }
+
+    fn frame_width(&self) -> f32;
+    fn frame_height(&self) -> f32;
+
+    fn translation(&self) -> (f32, f32);
+    fn scaling(&self) -> (f32, f32);
+    fn set_translation(&mut self, x: f32, y: f32);
+    fn set_scale(&mut self, x: f32, y: f32);
+
+    //Drawing is limited to the rect until pop_clip. A canvas that can't clip says so in can_clip
+    //and the GUI only draws what fits inside the rect on it.
+    fn can_clip(&self) -> bool;
+    fn push_clip(&mut self, x: f32, y: f32, width: f32, height: f32);
+    fn pop_clip(&mut self);
+
+    fn clear(&mut self, color: Color);
+    fn update(&mut self) {}
+    fn flush(&mut self);
+}
+
+//One recorded drawing call, positions and sizes are in frame coordinates with translation and scale already applied
+#[derive(Clone)]
+pub enum DrawCommand<T, F> {
+    Color(Color),
+    Font(F),
+    Rect { x: f32, y: f32, width: f32, height: f32 },
+    TexturedRect { x: f32, y: f32, width: f32, height: f32, texture: T },
+    SdfRect { x: f32, y: f32, width: f32, height: f32, texture: T, edge: f32, softness: f32 },
+    Text { text: String, x: f32, y: f32 },
+    PushClip { x: f32, y: f32, width: f32, height: f32 },
+    PopClip,
+}
+
+pub fn replay<C: Canvas>(canvas: &mut C, commands: &[DrawCommand<C::Texture, C::Font>]) {
+    let translation = canvas.translation();
+    let scaling = canvas.scaling();
+
+    canvas.set_translation(0.0, 0.0);
+    canvas.set_scale(1.0, 1.0);
+
+    for command in commands {
+        match command {
+            DrawCommand::Color(color) => canvas.set_color(*color),
+            DrawCommand::Font(font) => canvas.set_font(font.clone()),
+            DrawCommand::Rect { x, y, width, height } => {
+                canvas.texture(C::empty_texture());
+                canvas.fill_rect(*x, *y, *width, *height);
+            }
+            DrawCommand::TexturedRect { x, y, width, height, texture } => {
+                canvas.texture(texture.clone());
+                canvas.fill_rect(*x, *y, *width, *height);
+            }
+            DrawCommand::SdfRect { x, y, width, height, texture, edge, softness } => {
+                canvas.texture(texture.clone());
+                canvas.fill_sdf(*x, *y, *width, *height, *edge, *softness);
+            }
+            DrawCommand::Text { text, x, y } => canvas.draw_string(text, *x, *y),
+            DrawCommand::PushClip { x, y, width, height } => canvas.push_clip(*x, *y, *width, *height),
+            DrawCommand::PopClip => canvas.pop_clip(),
+        }
+    }
+
+    canvas.set_translation(translation.0, translation.1);
+    canvas.set_scale(scaling.0, scaling.1);
+}
+
+//Graphics together with the font set on it, Graphics only keeps the engine Font which can't tell where its baseline is
+pub struct GraphicsCanvas {
+    pub graphics: Graphics,
+    font: TrueTypeFont,
+}
+
+impl GraphicsCanvas {
+    pub fn new(graphics: Graphics) -> GraphicsCanvas {
+        let font = TrueTypeFont::from_font(graphics.font().clone());
+        GraphicsCanvas {
+            graphics,
+            font,
+        }
+    }
+
+    //Graphics can't threshold the atlas, so sdf text is rasterized on the CPU here: the GUI fills the glyphs
+    //from the atlas image as plain rects, one for every run of covered pixels, and they need no texture
+    pub fn sdf_font(atlas: &SdfAtlas, size: f32) -> SdfFont<TextureRegion> {
+        SdfFont::new(atlas, size, |_, _, _, _| TextureRegion::new_invalid())
+    }
+}
+
+impl Canvas for GraphicsCanvas {
+    type Texture = TextureRegion;
+    type Font = TrueTypeFont;
+
+    fn empty_texture() -> TextureRegion {
+        TextureRegion::new_invalid()
+    }
+
+    fn set_color(&mut self, color: Color) {
+        self.graphics.set_color(color);
+    }
+
+    fn texture(&mut self, texture: TextureRegion) {
+        self.graphics.texture(texture);
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.graphics.fill_rect(x, y, width, height);
+    }
+
+    //Graphics has no shader that thresholds a texture
+    fn can_fill_sdf(&self) -> bool {
+        false
+    }
+
+    fn fill_sdf(&mut self, _x: f32, _y: f32, _width: f32, _height: f32, _edge: f32, _softness: f32) {}
+
+    fn draw_string(&mut self, text: &str, x: f32, y: f32) {
+        self.graphics.draw_string(text, x, y);
+    }
+
+    fn font(&self) -> &TrueTypeFont {
+        &self.font
+    }
+
+    fn set_font(&mut self, font: TrueTypeFont) {
+        self.graphics.set_font(font.font.clone());
+        self.font = font;
+    }
+
+    fn text_width(&self, text: &str) -> f32 {
+        self.font.text_width(text)
+    }
+
+    fn font_height(&self) -> f32 {
+        self.font.height()
+    }
+
+    fn frame_width(&self) -> f32 {
+        self.graphics.frame_width() as f32
+    }
+
+    fn frame_height(&self) -> f32 {
+        self.graphics.frame_height() as f32
+    }
+
+    fn translation(&self) -> (f32, f32) {
+        self.graphics.translation()
+    }
+
+    fn scaling(&self) -> (f32, f32) {
+        self.graphics.scaling()
+    }
+
+    fn set_translation(&mut self, x: f32, y: f32) {
+        self.graphics.set_translation(x, y);
+    }
+
+    fn set_scale(&mut self, x: f32, y: f32) {
+        self.graphics.set_scale(x, y);
+    }
+
+    //Graphics has no scissor state
+    fn can_clip(&self) -> bool {
+        false
+    }
+
+    fn push_clip(&mut self, _x: f32, _y: f32, _width: f32, _height: f32) {}
+
+    fn pop_clip(&mut self) {}
+
+    fn clear(&mut self, color: Color) {
+        self.graphics.clear(color);
+    }
+
+    fn update(&mut self) {
+        self.graphics.update();
+    }
+
+    fn flush(&mut self) {
+        self.graphics.flush();
+    }
+}
@@ -0,0 +1,364 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::raster::{Bitmap, BitmapFont, ImageRegion, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::ttf::{FontError, FontFile};
+
+const ATLAS_COLUMNS: usize = 16;
+
+//A straight piece of an outline from one point to the next
+type Segment = ((f32, f32), (f32, f32));
+
+//Where a glyph's cell is in the atlas and how it sits on the baseline, in atlas pixels
+#[derive(Clone)]
+struct AtlasGlyph {
+    x: usize,
+    y: usize,
+    width: usize, //0 for glyphs with nothing to draw, like space
+    height: usize,
+    left: f32, //from the pen to the left edge of the cell
+    bottom: f32, //from the baseline up to the bottom edge of the cell, negative below it
+    advance: f32,
+}
+
+//Glyphs as a signed distance field, the alpha of a pixel is 0.5 on the edge of a glyph and goes up
+//inside and down outside it by 0.5 every spread pixels. Thresholding that stays sharp at any size.
+pub struct SdfAtlas {
+    pub image: Rc<Bitmap>,
+    pub spread: usize,
+    pub ascent: f32, //atlas pixels above the baseline
+    pub descent: f32, //atlas pixels below it
+
+    glyphs: HashMap<char, AtlasGlyph>,
+}
+
+impl SdfAtlas {
+    //The glyphs of a BitmapFont, resolution atlas pixels to every pixel of the font
+    pub fn new(font: &BitmapFont, resolution: usize, spread: usize) -> SdfAtlas {
+        let spread = spread.max(1); //the distances are divided by it
+        let mut chars = font.chars().collect::<Vec<char>>();
+        chars.sort_unstable();
+
+        let cell_width = GLYPH_WIDTH * resolution + 2 * spread;
+        let cell_height = GLYPH_HEIGHT * resolution + 2 * spread;
+        let rows = chars.len().div_ceil(ATLAS_COLUMNS);
+
+        let mut image = Bitmap::new(cell_width * ATLAS_COLUMNS, cell_height * rows.max(1));
+        let mut glyphs = HashMap::new();
+
+        for (i, ch) in chars.iter().enumerate() {
+            let cell = ((i % ATLAS_COLUMNS) * cell_width, (i / ATLAS_COLUMNS) * cell_height);
+            let columns = font.glyph(*ch);
+
+            for y in 0..cell_height {
+                for x in 0..cell_width {
+                    //the center of the pixel relative to the top left of the glyph
+                    let px = x as f32 + 0.5 - spread as f32;
+                    let py = y as f32 + 0.5 - spread as f32;
+
+                    let distance = signed_distance(&columns, resolution as f32, px, py);
+                    image.set_pixel(cell.0 + x, cell.1 + y, distance_pixel(distance, spread));
+                }
+            }
+            glyphs.insert(*ch, AtlasGlyph {
+                x: cell.0,
+                y: cell.1,
+                width: cell_width,
+                height: cell_height,
+                left: -(spread as f32),
+                bottom: -((font.descent * resolution + spread) as f32),
+                advance: ((GLYPH_WIDTH + 1) * resolution) as f32,
+            });
+        }
+
+        SdfAtlas {
+            image: Rc::new(image),
+            spread,
+            ascent: ((GLYPH_HEIGHT - font.descent) * resolution) as f32,
+            descent: (font.descent * resolution) as f32,
+            glyphs,
+        }
+    }
+
+    //The glyphs of chars from the outlines in a TrueType file, height atlas pixels from the lowest
+    //descender to the highest ascender. Characters the file has no glyph for are left out.
+    pub fn from_font_file<I: IntoIterator<Item = char>>(file: &FontFile, chars: I, height: usize, spread: usize) -> Result<SdfAtlas, FontError> {
+        let spread = spread.max(1);
+        let mut chars = chars.into_iter().collect::<Vec<char>>();
+        chars.sort_unstable();
+        chars.dedup();
+
+        let scale = height as f32 / (file.ascender as f32 - file.descender as f32);
+
+        //the outline of every glyph in pixels with y going down from the top of its cell, and the cell
+        let mut outlines = Vec::new();
+        for ch in chars {
+            let glyph = match file.glyph_index(ch) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            let advance = file.advance(glyph)? as f32 * scale;
+            let contours = file.outline(glyph)?;
+
+            let points = contours.iter().flatten();
+            let (min_x, min_y) = points.clone().fold((f32::MAX, f32::MAX), |(x, y), point| (x.min(point.0), y.min(point.1)));
+            let (max_x, max_y) = points.fold((f32::MIN, f32::MIN), |(x, y), point| (x.max(point.0), y.max(point.1)));
+
+            if contours.is_empty() || max_x <= min_x || max_y <= min_y {
+                outlines.push((ch, Vec::new(), AtlasGlyph { x: 0, y: 0, width: 0, height: 0, left: 0.0, bottom: 0.0, advance }));
+                continue;
+            }
+
+            let left = (min_x * scale).floor() - spread as f32;
+            let bottom = (min_y * scale).floor() - spread as f32;
+            let width = ((max_x * scale).ceil() - left) as usize + spread;
+            let height = ((max_y * scale).ceil() - bottom) as usize + spread;
+
+            let top = bottom + height as f32;
+            let segments = contours.iter().flat_map(|contour| {
+                let cell_point = |point: &(f32, f32)| (point.0 * scale - left, top - point.1 * scale);
+                contour.iter().zip(contour.iter().cycle().skip(1)).map(move |(a, b)| (cell_point(a), cell_point(b)))
+            }).collect::<Vec<Segment>>();
+
+            outlines.push((ch, segments, AtlasGlyph { x: 0, y: 0, width, height, left, bottom, advance }));
+        }
+
+        //cells go in rows from the top left, a row is as high as its highest cell
+        let area = outlines.iter().map(|(_, _, glyph)| glyph.width * glyph.height).sum::<usize>();
+        let widest = outlines.iter().map(|(_, _, glyph)| glyph.width).max().unwrap_or(0);
+        let atlas_width = widest.max((area as f32).sqrt().ceil() as usize).max(1);
+
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+        for (_, _, glyph) in outlines.iter_mut() {
+            if x + glyph.width > atlas_width {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+            glyph.x = x;
+            glyph.y = y;
+            x += glyph.width;
+            row_height = row_height.max(glyph.height);
+        }
+
+        let mut image = Bitmap::new(atlas_width, (y + row_height).max(1));
+        let mut glyphs = HashMap::new();
+        for (ch, segments, glyph) in outlines {
+            for py in 0..glyph.height {
+                for px in 0..glyph.width {
+                    let distance = outline_distance(&segments, px as f32 + 0.5, py as f32 + 0.5);
+                    image.set_pixel(glyph.x + px, glyph.y + py, distance_pixel(distance, spread));
+                }
+            }
+            glyphs.insert(ch, glyph);
+        }
+
+        Ok(SdfAtlas {
+            image: Rc::new(image),
+            spread,
+            ascent: file.ascender as f32 * scale,
+            descent: -file.descender as f32 * scale,
+            glyphs,
+        })
+    }
+
+    //x, y, width and height of the cell ch is in, the glyph sits spread pixels in from its edges
+    pub fn glyph_rect(&self, ch: char) -> Option<(usize, usize, usize, usize)> {
+        self.glyphs.get(&ch).map(|glyph| (glyph.x, glyph.y, glyph.width, glyph.height))
+    }
+}
+
+fn distance_pixel(distance: f32, spread: usize) -> [u8; 4] {
+    let value = (0.5 + distance / (2.0 * spread as f32)).clamp(0.0, 1.0);
+    [255, 255, 255, (value * 255.0).round() as u8]
+}
+
+//Distance in atlas pixels from x, y to the edge of the glyph, positive inside it
+fn signed_distance(columns: &[u8; GLYPH_WIDTH], resolution: f32, x: f32, y: f32) -> f32 {
+    let inside = |col: usize, row: usize| columns[col] & (1 << row) != 0;
+
+    let col = (x / resolution).floor();
+    let row = (y / resolution).floor();
+    let is_inside = col >= 0.0 && row >= 0.0 && (col as usize) < GLYPH_WIDTH && (row as usize) < GLYPH_HEIGHT && inside(col as usize, row as usize);
+
+    let to_square = |col: usize, row: usize| {
+        let left = col as f32 * resolution;
+        let top = row as f32 * resolution;
+        let dx = (left - x).max(x - left - resolution).max(0.0);
+        let dy = (top - y).max(y - top - resolution).max(0.0);
+        (dx * dx + dy * dy).sqrt()
+    };
+
+    let mut nearest = f32::MAX;
+    if is_inside {
+        //everything around the glyph is outside too
+        let width = GLYPH_WIDTH as f32 * resolution;
+        let height = GLYPH_HEIGHT as f32 * resolution;
+        nearest = x.min(y).min(width - x).min(height - y);
+    }
+
+    for col in 0..GLYPH_WIDTH {
+        for row in 0..GLYPH_HEIGHT {
+            if inside(col, row) != is_inside {
+                nearest = nearest.min(to_square(col, row));
+            }
+        }
+    }
+
+    if is_inside {
+        nearest
+    } else {
+        -nearest
+    }
+}
+
+//Distance from x, y to the closest segment of an outline, positive inside it. Inside is where the
+//contours wind around the point a nonzero number of times, like TrueType fills them.
+fn outline_distance(segments: &[Segment], x: f32, y: f32) -> f32 {
+    let mut winding = 0;
+    let mut nearest = f32::MAX;
+
+    for (a, b) in segments {
+        let cross = (b.0 - a.0) * (y - a.1) - (x - a.0) * (b.1 - a.1);
+        if a.1 <= y && b.1 > y && cross > 0.0 {
+            winding += 1;
+        } else if b.1 <= y && a.1 > y && cross < 0.0 {
+            winding -= 1;
+        }
+
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length = dx * dx + dy * dy;
+        let t = if length > 0.0 { (((x - a.0) * dx + (y - a.1) * dy) / length).clamp(0.0, 1.0) } else { 0.0 };
+        let (cx, cy) = (a.0 + t * dx - x, a.1 + t * dy - y);
+        nearest = nearest.min((cx * cx + cy * cy).sqrt());
+    }
+
+    if winding != 0 {
+        nearest
+    } else {
+        -nearest
+    }
+}
+
+//One glyph placed by SdfFont::layout, in the coordinates given to it
+pub struct SdfQuad<T> {
+    pub texture: T,
+    pub region: ImageRegion, //the glyph's cell in the atlas image
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+//An SdfAtlas turned into textures of a canvas, sized in the same units as the bounds of the widgets
+//so the text scales with the transform like everything else
+pub struct SdfFont<T> {
+    pub size: f32, //height of the glyphs from the lowest descender to the highest ascender
+    pub image: Rc<Bitmap>, //the atlas, a canvas that can't threshold a texture has the GUI fill the glyphs from it
+
+    glyphs: HashMap<char, (Option<T>, AtlasGlyph)>,
+    spread: f32,
+    ascent: f32, //in atlas pixels like the glyphs
+    line_height: f32,
+    space: f32, //advance of the characters the atlas has no glyph for
+}
+
+impl<T: Clone> SdfFont<T> {
+    //region makes a texture out of the x, y, width and height of a part of the atlas image
+    pub fn new<F: FnMut(usize, usize, usize, usize) -> T>(atlas: &SdfAtlas, size: f32, mut region: F) -> SdfFont<T> {
+        let glyphs = atlas.glyphs.iter().map(|(ch, glyph)| {
+            let texture = if glyph.width > 0 { Some(region(glyph.x, glyph.y, glyph.width, glyph.height)) } else { None };
+            (*ch, (texture, glyph.clone()))
+        }).collect::<HashMap<char, (Option<T>, AtlasGlyph)>>();
+
+        let line_height = atlas.ascent + atlas.descent;
+        let space = match atlas.glyphs.get(&' ') {
+            Some(glyph) => glyph.advance,
+            None => line_height / 2.0,
+        };
+
+        SdfFont {
+            size,
+            image: atlas.image.clone(),
+            glyphs,
+            spread: atlas.spread.max(1) as f32,
+            ascent: atlas.ascent,
+            line_height,
+            space,
+        }
+    }
+
+    //How far the pen moves after ch in units of size
+    pub fn advance(&self, ch: char) -> f32 {
+        let advance = self.glyphs.get(&ch).map_or(self.space, |(_, glyph)| glyph.advance);
+        advance * self.size / self.line_height
+    }
+
+    //Width of text in units of size
+    pub fn text_width(&self, text: &str) -> f32 {
+        text.graphemes(true).map(|grapheme| self.advance(grapheme.chars().next().unwrap_or(' '))).sum()
+    }
+
+    //how far the glyphs reach above and below the baseline in units of size, together they make up size
+    pub fn ascent(&self) -> f32 {
+        self.size * self.ascent / self.line_height
+    }
+
+    pub fn descent(&self) -> f32 {
+        self.size - self.ascent()
+    }
+
+    //The cell of every character with the baseline at y, scaled by scale.
+    //Characters the atlas has no glyph for only take up space.
+    pub fn layout(&self, text: &str, x: f32, y: f32, scale: (f32, f32)) -> Vec<SdfQuad<T>> {
+        let unit = self.size / self.line_height; //size units per atlas pixel
+
+        let mut quads = Vec::new();
+        let mut pen_x = x;
+        for grapheme in text.graphemes(true) {
+            let ch = grapheme.chars().next().unwrap_or(' ');
+            if let Some((Some(texture), glyph)) = self.glyphs.get(&ch) {
+                quads.push(SdfQuad {
+                    texture: texture.clone(),
+                    region: ImageRegion::new(glyph.x, glyph.y, glyph.width, glyph.height, &self.image),
+                    x: pen_x + glyph.left * unit * scale.0,
+                    y: y + glyph.bottom * unit * scale.1,
+                    width: glyph.width as f32 * unit * scale.0,
+                    height: glyph.height as f32 * unit * scale.1,
+                });
+            }
+            pen_x += self.advance(ch) * scale.0;
+        }
+        quads
+    }
+
+    //How much of the distance field one unit of size covers, to turn pixel widths into thresholds
+    pub fn distance_per_unit(&self) -> f32 {
+        self.line_height / self.size / (2.0 * self.spread)
+    }
+}
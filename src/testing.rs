@@ -0,0 +1,233 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::comps::{Button, CheckBox, Slider, TextBox};
+use crate::gui::{GUI, GUIStyle};
+use crate::input::ScriptedInput;
+use crate::raster::{Bitmap, BitmapFont, ImageRegion, SoftwareCanvas};
+
+pub type SoftwareStyle = GUIStyle<Option<ImageRegion>, BitmapFont>;
+
+pub struct Widgets {
+    pub button: Button,
+    pub slider: Slider,
+    pub check_box: CheckBox,
+    pub text_box: TextBox,
+}
+
+impl Widgets {
+    pub fn new() -> Widgets {
+        let mut button = Button::new();
+        button.x = -0.9;
+        button.y = 0.4;
+        button.width = 0.8;
+        button.height = 0.4;
+        button.text = String::from("button");
+
+        let mut check_box = CheckBox::new();
+        check_box.x = 0.1;
+        check_box.y = 0.4;
+        check_box.width = 0.3;
+        check_box.height = 0.3;
+
+        let mut slider = Slider::new();
+        slider.x = -0.9;
+        slider.y = 0.0;
+        slider.width = 1.8;
+        slider.height = 0.05;
+        slider.val = 0.5;
+
+        let mut text_box = TextBox::new();
+        text_box.x = -0.9;
+        text_box.y = -0.8;
+        text_box.width = 1.8;
+        text_box.height = 0.4;
+        text_box.text = String::from("text");
+
+        Widgets {
+            button,
+            slider,
+            check_box,
+            text_box,
+        }
+    }
+}
+
+//Feeds the script to the GUI, then draws one frame of all widgets and returns the pixels
+pub fn render_frame<F>(style: SoftwareStyle, width: usize, height: usize, widgets: &mut Widgets, script: F) -> Bitmap
+    where F: FnOnce(&mut ScriptedInput)
+{
+    let mut gui = GUI::with_canvas(SoftwareCanvas::new(width, height), ScriptedInput::new());
+    gui.style = style;
+
+    script(&mut gui.input);
+    gui.update();
+
+    gui.clear();
+    gui.button(&mut widgets.button);
+    gui.slider(&mut widgets.slider);
+    gui.check_box(&mut widgets.check_box);
+    gui.text_box(&mut widgets.text_box);
+    gui.update();
+
+    gui.graphics.target
+}
+
+pub enum SnapshotError {
+    Missing(PathBuf),
+    Io(std::io::Error),
+    Decoding(png::DecodingError),
+    Encoding(png::EncodingError),
+    Format(PathBuf),
+    Size { expected: (usize, usize), actual: (usize, usize) },
+    Pixels { differing: usize, max_difference: u8, diff: PathBuf },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Missing(path) => write!(f, "there is no reference at {}, run with UPDATE_SNAPSHOTS set to write it", path.display()),
+            SnapshotError::Io(err) => write!(f, "io error: {}", err),
+            SnapshotError::Decoding(err) => write!(f, "could not decode reference: {}", err),
+            SnapshotError::Encoding(err) => write!(f, "could not encode image: {}", err),
+            SnapshotError::Format(path) => write!(f, "{} is not an 8 bit rgba png", path.display()),
+            SnapshotError::Size { expected, actual } => write!(f, "size {:?} does not match reference size {:?}", actual, expected),
+            SnapshotError::Pixels { differing, max_difference, diff } => write!(f, "{} pixels differ by up to {}, diff written to {}", differing, max_difference, diff.display()),
+        }
+    }
+}
+
+impl fmt::Debug for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(err: std::io::Error) -> SnapshotError {
+        SnapshotError::Io(err)
+    }
+}
+
+impl From<png::DecodingError> for SnapshotError {
+    fn from(err: png::DecodingError) -> SnapshotError {
+        SnapshotError::Decoding(err)
+    }
+}
+
+impl From<png::EncodingError> for SnapshotError {
+    fn from(err: png::EncodingError) -> SnapshotError {
+        SnapshotError::Encoding(err)
+    }
+}
+
+pub fn write_png(path: &Path, image: &Bitmap) -> Result<(), SnapshotError> {
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(writer, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&image.pixels)?;
+    Ok(())
+}
+
+pub fn read_png(path: &Path) -> Result<Bitmap, SnapshotError> {
+    let decoder = png::Decoder::new(File::open(path)?);
+    let (info, mut reader) = decoder.read_info()?;
+
+    if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+        return Err(SnapshotError::Format(path.to_path_buf()));
+    }
+
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels)?;
+    Ok(Bitmap::from_rgba(info.width as usize, info.height as usize, pixels))
+}
+
+//Compares the image against the png at path, channels may differ by at most tolerance.
+//With UPDATE_SNAPSHOTS set the image is written as the new reference instead, a missing reference is an error otherwise.
+//On a mismatch a diff image with the differing pixels in red is written next to the reference.
+pub fn compare_snapshot(path: &Path, image: &Bitmap, tolerance: u8) -> Result<(), SnapshotError> {
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        return write_png(path, image);
+    }
+
+    if !path.exists() {
+        return Err(SnapshotError::Missing(path.to_path_buf()));
+    }
+
+    let reference = read_png(path)?;
+    if (reference.width, reference.height) != (image.width, image.height) {
+        return Err(SnapshotError::Size {
+            expected: (reference.width, reference.height),
+            actual: (image.width, image.height),
+        });
+    }
+
+    let mut diff = Bitmap::new(image.width, image.height);
+    let mut differing = 0;
+    let mut max_difference = 0;
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let expected = reference.pixel(x, y);
+            let actual = image.pixel(x, y);
+            let difference = expected.iter().zip(actual.iter()).map(|(e, a)| e.max(a) - e.min(a)).max().unwrap_or(0);
+
+            max_difference = max_difference.max(difference);
+            if difference > tolerance {
+                differing += 1;
+                diff.set_pixel(x, y, [255, 0, 0, 255]);
+            } else {
+                diff.set_pixel(x, y, [expected[0] / 4, expected[1] / 4, expected[2] / 4, 255]);
+            }
+        }
+    }
+
+    if differing == 0 {
+        return Ok(());
+    }
+
+    let diff_path = path.with_extension("diff.png");
+    write_png(&diff_path, &diff)?;
+    write_png(&path.with_extension("actual.png"), image)?;
+
+    Err(SnapshotError::Pixels {
+        differing,
+        max_difference,
+        diff: diff_path,
+    })
+}
+
+pub fn assert_snapshot<P: AsRef<Path>>(path: P, image: &Bitmap, tolerance: u8) {
+    if let Err(err) = compare_snapshot(path.as_ref(), image, tolerance) {
+        panic!("snapshot {} failed: {}", path.as_ref().display(), err);
+    }
+}
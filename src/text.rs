@@ -0,0 +1,182 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use unicode_segmentation::UnicodeSegmentation;
+
+//Everything here steps over grapheme clusters, what the user sees as one character,
+//so an e with a combining accent or a flag emoji is never split
+
+//Byte index of the character boundary before index, or 0
+pub fn prev_boundary(text: &str, index: usize) -> usize {
+    text[..index].grapheme_indices(true).next_back().map(|(i, _)| i).unwrap_or(0)
+}
+
+//Byte index of the character boundary after index, or the length of the text
+pub fn next_boundary(text: &str, index: usize) -> usize {
+    match text[index..].graphemes(true).next() {
+        Some(grapheme) => index + grapheme.len(),
+        None => text.len(),
+    }
+}
+
+//Moves index back onto a character boundary if it ended up inside one or past the end
+pub fn clamp_boundary(text: &str, index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+    text.grapheme_indices(true).map(|(i, _)| i).take_while(|i| *i <= index).last().unwrap_or(0)
+}
+
+//Number of characters in text
+pub fn count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+//Byte indices of every character boundary in text, from 0 to the length of the text
+pub fn boundaries(text: &str) -> Vec<usize> {
+    let mut boundaries = text.grapheme_indices(true).map(|(i, _)| i).collect::<Vec<usize>>();
+    boundaries.push(text.len());
+    boundaries
+}
+
+//The measuring functions below binary search the character boundaries, as prefixes only get wider
+//that takes log n measurements instead of one for every character
+
+//End of the longest prefix of text that is at most max_width wide
+pub fn fit<F: Fn(&str) -> f32>(text: &str, max_width: f32, measure: F) -> usize {
+    let boundaries = boundaries(text);
+    let fitting = boundaries.partition_point(|i| measure(&text[..*i]) <= max_width);
+    boundaries[fitting.max(1) - 1]
+}
+
+//Start of the longest suffix of text that is at most max_width wide
+pub fn fit_suffix<F: Fn(&str) -> f32>(text: &str, max_width: f32, measure: F) -> usize {
+    let boundaries = boundaries(text);
+    let too_wide = boundaries.partition_point(|i| measure(&text[*i..]) > max_width);
+    boundaries[too_wide.min(boundaries.len() - 1)]
+}
+
+//The character boundary closest to x, measured from the start of text
+pub fn index_at<F: Fn(&str) -> f32>(text: &str, x: f32, measure: F) -> usize {
+    let boundaries = boundaries(text);
+    let before = boundaries.partition_point(|i| measure(&text[..*i]) < x);
+
+    if before == 0 {
+        return 0;
+    }
+    if before == boundaries.len() {
+        return text.len();
+    }
+
+    let (left, right) = (boundaries[before - 1], boundaries[before]);
+    if measure(&text[..right]) - x < x - measure(&text[..left]) {
+        right
+    } else {
+        left
+    }
+}
+
+//Byte range of the characters that are at least partly between the pixel offsets from and to, measured from the start of text
+pub fn visible_range<F: Fn(&str) -> f32>(text: &str, from: f32, to: f32, measure: F) -> (usize, usize) {
+    let boundaries = boundaries(text);
+    let hidden = boundaries.partition_point(|i| measure(&text[..*i]) <= from);
+    let start = boundaries[hidden.max(1) - 1];
+    let end = boundaries.get(boundaries.partition_point(|i| measure(&text[..*i]) < to)).copied().unwrap_or(text.len());
+
+    (start, end.max(start))
+}
+
+//Byte range of the characters that are wholly between the pixel offsets from and to, measured from the start of text
+pub fn inside_range<F: Fn(&str) -> f32>(text: &str, from: f32, to: f32, measure: F) -> (usize, usize) {
+    let boundaries = boundaries(text);
+    let start = boundaries[boundaries.partition_point(|i| measure(&text[..*i]) < from).min(boundaries.len() - 1)];
+    let end = boundaries[boundaries.partition_point(|i| measure(&text[..*i]) <= to).max(1) - 1];
+
+    (start, end.max(start))
+}
+
+pub const ELLIPSIS: &str = "\u{2026}";
+
+//Shortens text to at most max_width by replacing characters at the end, or in the middle, with "…"
+pub fn ellipsize<F: Fn(&str) -> f32>(text: &str, max_width: f32, middle: bool, measure: F) -> String {
+    if measure(text) <= max_width {
+        return String::from(text);
+    }
+
+    let available = max_width - measure(ELLIPSIS);
+    if middle {
+        let head = fit(text, available / 2.0, &measure);
+        let tail = head + fit_suffix(&text[head..], available - measure(&text[..head]), &measure);
+        format!("{}{}{}", &text[..head], ELLIPSIS, &text[tail..])
+    } else {
+        format!("{}{}", &text[..fit(text, available, &measure)], ELLIPSIS)
+    }
+}
+
+//Splits text into lines at '\n' and wherever it gets wider than max_width, preferring to break after a space.
+//Spaces are allowed to hang past max_width so a line never starts with the space it was broken at.
+//Returns the byte range of each line without the '\n', there is always at least one line.
+pub fn wrap_lines<F: Fn(&str) -> f32>(text: &str, max_width: f32, measure: F) -> Vec<(usize, usize)> {
+    let mut lines = Vec::new();
+
+    let mut paragraph_start = 0;
+    for paragraph in text.split('\n') {
+        let paragraph_end = paragraph_start + paragraph.len();
+        let paragraph_boundaries = boundaries(paragraph).iter().map(|i| paragraph_start + i).collect::<Vec<usize>>();
+
+        let mut first = 0;
+        while paragraph_boundaries[first] < paragraph_end {
+            let line_start = paragraph_boundaries[first];
+            let rest = &paragraph_boundaries[first..];
+
+            //as many characters as fit but at least one
+            let mut last = rest.partition_point(|i| measure(&text[line_start..*i]) <= max_width).max(2) - 1;
+
+            while rest[last] < paragraph_end && text[rest[last]..].starts_with(' ') {
+                last += 1;
+            }
+
+            if rest[last] < paragraph_end {
+                if let Some(space) = text[line_start..rest[last]].rfind(' ') {
+                    last = rest.partition_point(|i| *i <= line_start + space);
+                }
+            }
+
+            lines.push((line_start, rest[last]));
+            first += last;
+        }
+
+        if paragraph.is_empty() {
+            lines.push((paragraph_start, paragraph_start));
+        }
+
+        paragraph_start = paragraph_end + 1;
+    }
+
+    lines
+}
+
+//Index of the line the caret is on, a caret at a wrapped line break belongs to the line after it
+pub fn line_of(lines: &[(usize, usize)], caret: usize) -> usize {
+    lines.iter().rposition(|(start, _)| *start <= caret).unwrap_or(0)
+}
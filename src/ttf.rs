@@ -0,0 +1,185 @@
+/*
+ *   Copyright (c) 2020 Ludwig Bogsveen
+ *   All rights reserved.
+
+ *   Permission is hereby granted, free of charge, to any person obtaining a copy
+ *   of this software and associated documentation files (the "Software"), to deal
+ *   in the Software without restriction, including without limitation the rights
+ *   to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ *   copies of the Software, and to permit persons to whom the Software is
+ *   furnished to do so, subject to the following conditions:
+ 
+ *   The above copyright notice and this permission notice shall be included in all
+ *   copies or substantial portions of the Software.
+ 
+ *   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ *   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ *   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ *   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ *   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ *   OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ *   SOFTWARE.
+ */
+
+use std::fmt;
+
+use ttf_parser::{Face, FaceParsingError, GlyphId, OutlineBuilder};
+
+//Curves are drawn as this many straight pieces
+const CURVE_STEPS: usize = 6;
+
+//The parts of a TrueType file the GUI reads itself through ttf-parser, the engine Font only measures and draws
+pub struct FontFile {
+    pub units_per_em: u16,
+    pub ascender: i16, //font units above the baseline
+    pub descender: i16, //font units below the baseline, negative
+    pub line_gap: i16,
+
+    data: Vec<u8>,
+}
+
+impl FontFile {
+    pub fn parse(data: Vec<u8>) -> Result<FontFile, FontError> {
+        let face = Face::parse(&data, 0)?;
+        let (units_per_em, ascender, descender, line_gap) = (face.units_per_em(), face.ascender(), face.descender(), face.line_gap());
+        if ascender <= descender {
+            return Err(FontError::Invalid("ascender and descender"));
+        }
+
+        Ok(FontFile {
+            units_per_em,
+            ascender,
+            descender,
+            line_gap,
+            data,
+        })
+    }
+
+    pub fn open(path: &str) -> Result<FontFile, FontError> {
+        FontFile::parse(std::fs::read(path)?)
+    }
+
+    //Which part of a height measured from the lowest descender to the highest ascender is above the baseline
+    pub fn ascent_fraction(&self) -> f32 {
+        self.ascender as f32 / (self.ascender as f32 - self.descender as f32)
+    }
+
+    //The glyph the font draws ch with, None if it has none and would draw its missing glyph box
+    pub fn glyph_index(&self, ch: char) -> Option<u16> {
+        match self.face().ok()?.glyph_index(ch) {
+            Some(GlyphId(0)) | None => None,
+            Some(GlyphId(glyph)) => Some(glyph),
+        }
+    }
+
+    //How far the pen moves after glyph, in font units
+    pub fn advance(&self, glyph: u16) -> Result<u16, FontError> {
+        self.face()?.glyph_hor_advance(GlyphId(glyph)).ok_or(FontError::Invalid("glyph"))
+    }
+
+    //The contours of glyph as closed polylines in font units with y going up, curves are split into straight pieces
+    pub fn outline(&self, glyph: u16) -> Result<Vec<Vec<(f32, f32)>>, FontError> {
+        let face = self.face()?;
+        if glyph >= face.number_of_glyphs() {
+            return Err(FontError::Invalid("glyph"));
+        }
+
+        //glyphs like space have no outline at all
+        let mut contours = Contours { contours: Vec::new() };
+        face.outline_glyph(GlyphId(glyph), &mut contours);
+        Ok(contours.contours)
+    }
+
+    //Parsing again is only reading the table directory, it was checked when the file was opened
+    fn face(&self) -> Result<Face<'_>, FontError> {
+        Ok(Face::parse(&self.data, 0)?)
+    }
+}
+
+struct Contours {
+    contours: Vec<Vec<(f32, f32)>>,
+}
+
+impl Contours {
+    fn pen(&self) -> (f32, f32) {
+        self.contours.last().and_then(|contour| contour.last()).copied().unwrap_or((0.0, 0.0))
+    }
+
+    fn line_to(&mut self, to: (f32, f32)) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.push(to);
+        }
+    }
+}
+
+impl OutlineBuilder for Contours {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.contours.push(vec![(x, y)]);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        Contours::line_to(self, (x, y));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let from = self.pen();
+        for step in 1..=CURVE_STEPS {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            Contours::line_to(self, (
+                u * u * from.0 + 2.0 * u * t * x1 + t * t * x,
+                u * u * from.1 + 2.0 * u * t * y1 + t * t * y,
+            ));
+        }
+    }
+
+    //only fonts with CFF outlines have cubic curves
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let from = self.pen();
+        for step in 1..=CURVE_STEPS {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            Contours::line_to(self, (
+                u * u * u * from.0 + 3.0 * u * u * t * x1 + 3.0 * u * t * t * x2 + t * t * t * x,
+                u * u * u * from.1 + 3.0 * u * u * t * y1 + 3.0 * u * t * t * y2 + t * t * t * y,
+            ));
+        }
+    }
+
+    //contours are taken as closed, the last point joins back to the first
+    fn close(&mut self) {}
+}
+
+pub enum FontError {
+    Io(std::io::Error),
+    Parse(FaceParsingError),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Io(err) => write!(f, "io error: {}", err),
+            FontError::Parse(err) => write!(f, "the font file can't be read: {}", err),
+            FontError::Invalid(what) => write!(f, "the font file has an invalid {}", what),
+        }
+    }
+}
+
+impl fmt::Debug for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl From<std::io::Error> for FontError {
+    fn from(err: std::io::Error) -> FontError {
+        FontError::Io(err)
+    }
+}
+
+impl From<FaceParsingError> for FontError {
+    fn from(err: FaceParsingError) -> FontError {
+        FontError::Parse(err)
+    }
+}